@@ -0,0 +1,14 @@
+//!
+//! Build Script
+//!
+//! Generates the Protobuf messages of `proto/` with the `protobuf` feature
+//!
+
+fn main() {
+    #[cfg(feature = "protobuf")]
+    {
+        println!("cargo:rerun-if-changed=proto");
+        prost_build::compile_protos(&["proto/streams_data.proto"], &["proto/"])
+            .expect("Failed to compile the Protobuf messages");
+    }
+}
@@ -8,6 +8,8 @@
 //! ```bash
 //!   cargo run --example e01-subscriber --release -- --seed <SEED> --channel <CHANNEL ADDRESS>
 //!   --announcement_tag <ANNOUNCEMENT TAG> [--message-id <MESSAGE ID>]
+//!   cargo run --example e01-subscriber --release -- --keystore <FILE> [--identity subscriber]
+//!   --channel <CHANNEL ADDRESS> --announcement_tag <ANNOUNCEMENT TAG>
 //! ```
 use clap::{App, Arg};
 use iota_streams::{
@@ -15,21 +17,39 @@ use iota_streams::{
     app_channels::api::tangle::{Address, Subscriber},
 };
 use poc::{
-    sample::{print_message_payload, make_random_seed},
-    transport::{build_transport, s_fetch_next_messages, FetchMessageContentType},
+    keystore,
+    payload::chunk::Reassembler,
+    sample::{make_random_seed, print_message_payload, registry},
+    seed::Seed,
+    transport::{
+        build_transport, reassemble_messages, s_fetch_next_messages, FetchMessageContentType,
+    },
 };
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let rseed = make_random_seed();
     let matches = App::new("Simple IOTA Streams Subscriber")
         .version("1.0")
         .arg(
             Arg::with_name("seed")
                 .short("s")
                 .long("seed")
-                .default_value(&rseed)
-                .takes_value(true),
+                .takes_value(true)
+                .help("Seed of the Subscriber, Default: random"),
+        )
+        .arg(
+            Arg::with_name("keystore")
+                .long("keystore")
+                .takes_value(true)
+                .conflicts_with("seed")
+                .help("Encrypted keystore file with the seed of the identity, created if it does not exist"),
+        )
+        .arg(
+            Arg::with_name("identity")
+                .long("identity")
+                .takes_value(true)
+                .default_value("subscriber")
+                .help("Identity name in the keystore, Default: subscriber"),
         )
         .arg(
             Arg::with_name("url")
@@ -72,7 +92,13 @@ async fn main() -> anyhow::Result<()> {
     let api_url = matches
         .value_of("url")
         .unwrap_or("https://nodes.comnet.thetangle.org:443");
-    let seed = matches.value_of("seed").unwrap();
+    let identity = matches.value_of("identity").unwrap();
+    let seed = match (matches.value_of("keystore"), matches.value_of("seed")) {
+        // The identity gets a random seed the first time
+        (Some(path), _) => keystore::load_or_generate_seed(path, identity)?,
+        (None, Some(value)) => Seed::from(value),
+        (None, None) => make_random_seed(),
+    };
     let channel_address = matches.value_of("channel_address").unwrap();
     let announcement_tag = matches.value_of("announcement_tag").unwrap();
     let message_id = matches.value_of("message_id").unwrap_or("");
@@ -83,12 +109,8 @@ async fn main() -> anyhow::Result<()> {
 
     // Create subscriber
     //
-    let mut subscriber = Subscriber::new(
-        seed,
-        matches.value_of("encoding").unwrap(),
-        PAYLOAD_BYTES,
-        transport.clone(),
-    );
+    let encoding = matches.value_of("encoding").unwrap();
+    let mut subscriber = Subscriber::new(seed.as_str(), encoding, PAYLOAD_BYTES, transport.clone());
 
     println!("Channel Address={}", channel_address);
     println!("Announcement Tag ID={}", announcement_tag);
@@ -109,24 +131,30 @@ async fn main() -> anyhow::Result<()> {
         subscriber.channel_address().unwrap()
     );
 
+    // Decoders of the published messages, with their JSON Schemas
+    //
+    let registry = registry();
+
     if message_id.is_empty() {
         // Lis all data linked in the channel
         //
+        let mut reassembler = Reassembler::new();
         let mut msg_list =
-            s_fetch_next_messages(
-                &mut subscriber,
-                FetchMessageContentType::TaggedPacket,
-                true
-            ).await;
+            s_fetch_next_messages(&mut subscriber, FetchMessageContentType::TaggedPacket, true)
+                .await;
 
         while msg_list.len() > 0 {
-            for (idx, (address, unwrapped_public, unwrapped_masked)) in msg_list.iter().enumerate()
-            {
-                print_message_payload(
-                    format!("{}.- Tagged ({})", idx, address),
-                    &unwrapped_public,
-                    &unwrapped_masked,
-                );
+            let payloads = reassemble_messages(&mut reassembler, msg_list);
+            for (idx, (address, payload)) in payloads.iter().enumerate() {
+                match payload {
+                    Ok((unwrapped_public, unwrapped_masked)) => print_message_payload(
+                        &registry,
+                        format!("{}.- Tagged ({})", idx, address),
+                        unwrapped_public,
+                        unwrapped_masked,
+                    ),
+                    Err(e) => eprintln!("\n {}.- Tagged ({}): {}\n", idx, address, e),
+                }
             }
             msg_list =
                 s_fetch_next_messages(&mut subscriber, FetchMessageContentType::TaggedPacket, true)
@@ -149,7 +177,12 @@ async fn main() -> anyhow::Result<()> {
             .await
             .unwrap();
 
-        print_message_payload(format!("{} - Tagged", message_id), &uw_public, &uw_masked);
+        print_message_payload(
+            &registry,
+            format!("{} - Tagged", message_id),
+            &uw_public,
+            &uw_masked,
+        );
     }
 
     Ok(())
@@ -8,6 +8,7 @@
 //!
 //! ```bash
 //!   cargo run --example e01-author --release -- --seed <SEED> [--mss-height 3]
+//!   cargo run --example e01-author --release -- --keystore <FILE> [--identity author]
 //! ```
 //!
 use clap::{App, Arg};
@@ -16,11 +17,13 @@ use iota_streams::{
     app_channels::api::tangle::{Address, Author, Transport},
 };
 use regex::Regex;
-use tokio::io::{AsyncBufReadExt, BufReader, stdin};
+use tokio::io::{stdin, AsyncBufReadExt, BufReader};
 
 use poc::{
-    payload::{json::PayloadBuilder, PacketPayload},
-    sample::{StreamsData, make_random_seed, get_message_index},
+    keystore,
+    payload::{batch::BatchPayload, json::JsonSerializer, PacketPayload},
+    sample::{get_message_index, make_random_seed, open_source},
+    seed::{self, Seed},
     transport::build_transport,
 };
 
@@ -28,15 +31,41 @@ use std::time::Duration;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let rseed = make_random_seed();
     let matches = App::new("Simple IOTA Streams Author")
         .version("1.0")
         .arg(
             Arg::with_name("seed")
                 .short("s")
                 .long("seed")
-                .default_value(&rseed)
-                .takes_value(true),
+                .takes_value(true)
+                .help("Seed of the Author, Default: random"),
+        )
+        .arg(
+            Arg::with_name("mnemonic")
+                .long("mnemonic")
+                .takes_value(true)
+                .conflicts_with("seed")
+                .help("Restore the seed from its 24 words BIP39 mnemonic"),
+        )
+        .arg(
+            Arg::with_name("show_mnemonic")
+                .long("show-mnemonic")
+                .conflicts_with_all(&["seed", "mnemonic", "keystore"])
+                .help("Print the 24 words BIP39 mnemonic of the random seed on stderr"),
+        )
+        .arg(
+            Arg::with_name("keystore")
+                .long("keystore")
+                .takes_value(true)
+                .conflicts_with_all(&["seed", "mnemonic"])
+                .help("Encrypted keystore file with the seed of the identity, created if it does not exist"),
+        )
+        .arg(
+            Arg::with_name("identity")
+                .long("identity")
+                .takes_value(true)
+                .default_value("author")
+                .help("Identity name in the keystore, Default: author"),
         )
         .arg(
             Arg::with_name("url")
@@ -59,25 +88,74 @@ async fn main() -> anyhow::Result<()> {
                 .default_value("utf-8")
                 .help("Encoding, Default UTF-8"),
         )
+        .arg(
+            Arg::with_name("batch_size")
+                .short("b")
+                .long("batch-size")
+                .takes_value(true)
+                .help("Readings packed in each message, Default: 1"),
+        )
+        .arg(
+            Arg::with_name("source")
+                .long("source")
+                .takes_value(true)
+                .help("Readings source: random, csv:<FILE> or sine[:<PERIOD>], Default: random"),
+        )
         .get_matches();
 
     let api_url = matches
         .value_of("url")
         .unwrap_or("https://nodes.comnet.thetangle.org:443");
 
-    let seed = matches.value_of("seed").unwrap();
+    let identity = matches.value_of("identity").unwrap();
+    let seed = match (
+        matches.value_of("keystore"),
+        matches.value_of("mnemonic"),
+        matches.value_of("seed"),
+    ) {
+        // The identity gets a random seed the first time
+        (Some(path), _, _) => keystore::load_or_generate_seed(path, identity)?,
+        (None, Some(words), _) => seed::from_mnemonic(words)?,
+        (None, None, Some(value)) => Seed::from(value),
+        (None, None, None) => make_random_seed(),
+    };
+    if matches.is_present("show_mnemonic") {
+        // Only a new random seed, on stderr so it does not end up in the logs of stdout
+        let words = seed::to_mnemonic(&seed)?;
+        eprintln!("Seed Mnemonic (Write it down to restore the Author):");
+        eprintln!("\t{}\n", words.as_str());
+    }
     let encoding = matches.value_of("encoding").unwrap();
     let mss_height: usize = matches
         .value_of("mss_height")
         .unwrap_or("3")
         .parse()
         .unwrap_or(3);
+    let batch_size: usize = matches
+        .value_of("batch_size")
+        .unwrap_or("1")
+        .parse()
+        .unwrap_or(1);
+    let source = matches.value_of("source").unwrap_or("random");
+    if source == "stdin" {
+        // The subscription links are read from stdin
+        return Err(anyhow::anyhow!(
+            "The stdin source is not supported, stdin reads the subscription links"
+        ));
+    }
+    let mut source = open_source(source).await?;
 
     let transport = build_transport(api_url, 9);
 
     // Create the author
     //
-    let mut author = Author::new(seed, encoding, PAYLOAD_BYTES, false, transport.clone());
+    let mut author = Author::new(
+        seed.as_str(),
+        encoding,
+        PAYLOAD_BYTES,
+        false,
+        transport.clone(),
+    );
 
     println!("\rChannel Address (Copy this Address for the Subscribers):");
     println!("\t{:?}\n", author.channel_address().unwrap());
@@ -98,7 +176,9 @@ async fn main() -> anyhow::Result<()> {
 
     let mut lines = BufReader::new(stdin()).lines();
 
-    println!("Type subscriber message ID (Example: SET e2feafdd5c6a72cef26ea3b2 ) and press enter \n");
+    println!(
+        "Type subscriber message ID (Example: SET e2feafdd5c6a72cef26ea3b2 ) and press enter \n"
+    );
 
     while let Some(line) = lines.next_line().await.unwrap() {
         if line.len() == 0 {
@@ -107,32 +187,41 @@ async fn main() -> anyhow::Result<()> {
         match Regex::new("SET\\s+\"(?P<target>[[:alnum:]]{20,32}?)\"") {
             Ok(regex) => {
                 if let Some(capture) = regex.captures(&line) {
-                    if let Some(subscriber_id) = capture.name("target").and_then(|s| Some(s.as_str().trim())) {
+                    if let Some(subscriber_id) =
+                        capture.name("target").and_then(|s| Some(s.as_str().trim()))
+                    {
                         println!("Sub ID: {} \n", &subscriber_id);
-                        author.receive_subscribe(
-                            &Address::from_str(
-                                &format!("{}", announcement_link.appinst),
-                                &subscriber_id).unwrap()
-                        ).await.unwrap();
+                        author
+                            .receive_subscribe(
+                                &Address::from_str(
+                                    &format!("{}", announcement_link.appinst),
+                                    &subscriber_id,
+                                )
+                                .unwrap(),
+                            )
+                            .await
+                            .unwrap();
 
                         println!("Added new subscriber {} \n", subscriber_id);
                     }
                 } else {
                     eprintln!("Missing argument subscriber id...")
                 }
-            },
-            Err(_) => println!("Try again ...")
+            }
+            Err(_) => println!("Try again ..."),
         }
     }
 
     let keyload_link = {
-        let (msg, _) = author.send_keyload_for_everyone(&announcement_link).await.unwrap();
+        let (msg, _) = author
+            .send_keyload_for_everyone(&announcement_link)
+            .await
+            .unwrap();
         println!("Keyload Message ID: {}", msg.msgid);
         println!("Author: {}", author);
         msg
     };
 
-
     // Total numbers of menssages to send
     //
     let remaining_sk = 2_u32.pow(mss_height as u32);
@@ -141,23 +230,33 @@ async fn main() -> anyhow::Result<()> {
     let mut linked_ = keyload_link.clone();
     // Announcement Link
     //
+    let mut batch = BatchPayload::new();
     loop {
-        if remaining_signed_messages <= 0 {
+        if remaining_signed_messages == 0 {
             break;
-        } else {
-            remaining_signed_messages = remaining_signed_messages - 1;
         }
-        let data = StreamsData::default();
-        println!("DATA={:?}", &data);
-        let _link_signed = send_signed_data(
-            &mut author,
-            &linked_,
-            PayloadBuilder::new().masked(&data)?.build(),
-        )
-        .await
-        .unwrap();
+        let data = source.next_reading().await?;
+        let exhausted = data.is_none();
+        if let Some(data) = data {
+            println!("DATA={:?}", &data);
+            batch.push(data);
+        }
+
+        // Pack as many readings as fit in one message, the rest wait for the next one
+        //
+        if batch.len() >= batch_size || (exhausted && !batch.is_empty()) {
+            if let Some(payload) = batch.pack_masked::<JsonSerializer>(PAYLOAD_BYTES)? {
+                let _link_signed = send_signed_data(&mut author, &linked_, payload)
+                    .await
+                    .unwrap();
 
-        linked_ = _link_signed;
+                linked_ = _link_signed;
+                remaining_signed_messages -= 1;
+            }
+        }
+        if exhausted {
+            break;
+        }
         tokio::time::sleep(Duration::from_secs(10)).await;
     }
 
@@ -7,7 +7,9 @@
 //!
 //! ```bash
 //!   cargo run --example e01-subscriber --release -- --seed <SEED> --channel <CHANNEL ADDRESS>
-//!   --announcement_tag <ANNOUNCEMENT TAG> [--message-id <MESSAGE ID>]
+//!   --announcement_tag <ANNOUNCEMENT TAG> [--message-id <MESSAGE ID>] [--group-keys <FILE>]
+//!   cargo run --example e01-subscriber --release -- --keystore <FILE> [--identity subscriber]
+//!   --channel <CHANNEL ADDRESS> --announcement_tag <ANNOUNCEMENT TAG>
 //! ```
 use clap::{App, Arg};
 use iota_streams::{
@@ -15,15 +17,19 @@ use iota_streams::{
     app_channels::api::tangle::{Address, Subscriber},
 };
 use poc::{
-    sample::{make_random_seed, print_message_payload},
-    transport::{build_transport, s_fetch_next_messages, FetchMessageContentType},
+    keystore,
+    payload::{chunk::Reassembler, encryption::GroupKeyRing},
+    sample::{make_random_seed, print_message_payload, registry},
+    seed::Seed,
+    transport::{
+        build_transport, reassemble_messages, s_fetch_next_messages, FetchMessageContentType,
+    },
 };
 use regex::Regex;
 use tokio::io::{stdin, AsyncBufReadExt, BufReader};
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let rseed = make_random_seed();
     let matches = App::new("Simple IOTA Streams Subscriber")
         .version("1.0")
         .arg(
@@ -31,7 +37,21 @@ async fn main() -> anyhow::Result<()> {
                 .short("s")
                 .long("seed")
                 .takes_value(true)
-                .default_value(&rseed),
+                .help("Seed of the Subscriber, Default: random"),
+        )
+        .arg(
+            Arg::with_name("keystore")
+                .long("keystore")
+                .takes_value(true)
+                .conflicts_with("seed")
+                .help("Encrypted keystore file with the seed of the identity, created if it does not exist"),
+        )
+        .arg(
+            Arg::with_name("identity")
+                .long("identity")
+                .takes_value(true)
+                .default_value("subscriber")
+                .help("Identity name in the keystore, Default: subscriber"),
         )
         .arg(
             Arg::with_name("url")
@@ -69,13 +89,25 @@ async fn main() -> anyhow::Result<()> {
                 .default_value("utf-8")
                 .help("Encoding, Default UTF-8"),
         )
+        .arg(
+            Arg::with_name("group_keys")
+                .long("group-keys")
+                .takes_value(true)
+                .help("Key store file with the group keys of the masked data"),
+        )
         .get_matches();
 
     let api_url = matches
         .value_of("url")
         .unwrap_or("https://nodes.comnet.thetangle.org:443");
 
-    let seed = matches.value_of("seed").unwrap();
+    let identity = matches.value_of("identity").unwrap();
+    let seed = match (matches.value_of("keystore"), matches.value_of("seed")) {
+        // The identity gets a random seed the first time
+        (Some(path), _) => keystore::load_or_generate_seed(path, identity)?,
+        (None, Some(value)) => Seed::from(value),
+        (None, None) => make_random_seed(),
+    };
     let channel_address = matches.value_of("channel_address").unwrap();
     let announcement_tag = matches.value_of("announcement_tag").unwrap();
     let message_id = matches.value_of("message_id").unwrap_or("");
@@ -86,12 +118,8 @@ async fn main() -> anyhow::Result<()> {
 
     // Create subscriber
     //
-    let mut subscriber = Subscriber::new(
-        seed,
-        matches.value_of("encoding").unwrap(),
-        PAYLOAD_BYTES,
-        transport.clone(),
-    );
+    let encoding = matches.value_of("encoding").unwrap();
+    let mut subscriber = Subscriber::new(seed.as_str(), encoding, PAYLOAD_BYTES, transport.clone());
 
     println!("Channel Address={}", channel_address);
     println!("Announcement Tag ID={}", announcement_tag);
@@ -156,20 +184,34 @@ async fn main() -> anyhow::Result<()> {
         }
     }
 
+    // Decoders of the published messages, with their JSON Schemas and the group keys to decrypt
+    // the masked data
+    //
+    let mut registry = registry();
+    if let Some(path) = matches.value_of("group_keys") {
+        registry.decrypt_with(GroupKeyRing::load(path)?);
+    }
+
     if message_id.is_empty() {
         // Lis all data linked in the channel
         //
+        let mut reassembler = Reassembler::new();
         let mut msg_list =
             s_fetch_next_messages(&mut subscriber, FetchMessageContentType::SignedPacket, true)
                 .await;
 
         while msg_list.len() > 0 {
-            for (idx, (msg, unwrapped_public, unwrapped_masked)) in msg_list.iter().enumerate() {
-                print_message_payload(
-                    format!("{}.- Signed ({})", idx, msg),
-                    &unwrapped_public,
-                    &unwrapped_masked,
-                );
+            let payloads = reassemble_messages(&mut reassembler, msg_list);
+            for (idx, (msg, payload)) in payloads.iter().enumerate() {
+                match payload {
+                    Ok((unwrapped_public, unwrapped_masked)) => print_message_payload(
+                        &registry,
+                        format!("{}.- Signed ({})", idx, msg),
+                        unwrapped_public,
+                        unwrapped_masked,
+                    ),
+                    Err(e) => eprintln!("\n {}.- Signed ({}): {}\n", idx, msg, e),
+                }
             }
             msg_list =
                 s_fetch_next_messages(&mut subscriber, FetchMessageContentType::SignedPacket, true)
@@ -192,7 +234,12 @@ async fn main() -> anyhow::Result<()> {
             .await
             .unwrap();
 
-        print_message_payload(format!("{} - Signed", message_id), &uw_public, &uw_masked);
+        print_message_payload(
+            &registry,
+            format!("{} - Signed", message_id),
+            &uw_public,
+            &uw_masked,
+        );
     }
 
     Ok(())
@@ -0,0 +1,209 @@
+//!
+//! PoC Derive Macros
+//!
+//! `#[derive(StreamsPayload)]` splits a struct in the public and masked parts of a payload.
+//! Fields marked with `#[masked]` go to the masked part, the others to the public part:
+//!
+//! ```ignore
+//! #[derive(StreamsPayload)]
+//! #[streams_payload(type_id = "sensor-reading", version = 1)]
+//! pub struct SensorReading {
+//!     ts: NaiveDateTime,
+//!     temperature: f32,
+//!     #[masked]
+//!     desc: String,
+//! }
+//! ```
+//!
+//! generates the `SensorReadingPublic` and `SensorReadingMasked` structs with the schema
+//! types `sensor-reading.public` and `sensor-reading.masked`. The type id defaults to the
+//! struct name in kebab case and the version to `1`.
+//!
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::{
+    parse_macro_input, Data, DeriveInput, Error, Field, Fields, Ident, Lit, Meta, NestedMeta,
+    Visibility,
+};
+
+#[proc_macro_derive(StreamsPayload, attributes(masked, streams_payload))]
+pub fn derive_streams_payload(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+///
+/// Container options, `#[streams_payload(type_id = "...", version = N)]`
+///
+struct Options {
+    type_id: String,
+    version: u16,
+}
+
+impl Options {
+    fn parse(input: &DeriveInput) -> syn::Result<Self> {
+        let mut options = Options {
+            type_id: kebab_case(&input.ident.to_string()),
+            version: 1,
+        };
+
+        for attr in input
+            .attrs
+            .iter()
+            .filter(|a| a.path.is_ident("streams_payload"))
+        {
+            let list = match attr.parse_meta()? {
+                Meta::List(list) => list,
+                other => {
+                    return Err(Error::new_spanned(
+                        other,
+                        "expected #[streams_payload(type_id = \"...\", version = N)]",
+                    ))
+                }
+            };
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("type_id") => {
+                        match nv.lit {
+                            Lit::Str(s) => options.type_id = s.value(),
+                            lit => return Err(Error::new_spanned(lit, "expected a string")),
+                        }
+                    }
+                    NestedMeta::Meta(Meta::NameValue(nv)) if nv.path.is_ident("version") => {
+                        match nv.lit {
+                            Lit::Int(i) => options.version = i.base10_parse()?,
+                            lit => return Err(Error::new_spanned(lit, "expected an integer")),
+                        }
+                    }
+                    other => {
+                        return Err(Error::new_spanned(other, "unknown streams_payload option"))
+                    }
+                }
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn kebab_case(name: &str) -> String {
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() && i > 0 {
+            out.push('-');
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
+
+fn is_masked(field: &Field) -> bool {
+    field.attrs.iter().any(|a| a.path.is_ident("masked"))
+}
+
+///
+/// Struct holding one part of the fields, keeps their `#[serde]` attributes
+///
+fn part_struct(vis: &Visibility, name: &Ident, fields: &[&Field]) -> TokenStream2 {
+    let decls = fields.iter().map(|f| {
+        let serde_attrs = f.attrs.iter().filter(|a| a.path.is_ident("serde"));
+        let (f_vis, f_ident, f_ty) = (&f.vis, &f.ident, &f.ty);
+        quote! { #(#serde_attrs)* #f_vis #f_ident: #f_ty }
+    });
+    quote! {
+        #[derive(Clone, ::serde::Serialize, ::serde::Deserialize)]
+        #vis struct #name {
+            #(#decls,)*
+        }
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "StreamsPayload does not support generic structs",
+        ));
+    }
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new_spanned(
+                    &input.ident,
+                    "StreamsPayload requires named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new_spanned(
+                &input.ident,
+                "StreamsPayload can only be derived for structs",
+            ))
+        }
+    };
+    let options = Options::parse(&input)?;
+
+    let (masked, public): (Vec<&Field>, Vec<&Field>) = fields.iter().partition(|f| is_masked(f));
+    let p_fields: Vec<_> = public.iter().map(|f| &f.ident).collect();
+    let m_fields: Vec<_> = masked.iter().map(|f| &f.ident).collect();
+
+    let ident = &input.ident;
+    let public_ident = format_ident!("{}Public", ident);
+    let masked_ident = format_ident!("{}Masked", ident);
+    let public_struct = part_struct(&input.vis, &public_ident, &public);
+    let masked_struct = part_struct(&input.vis, &masked_ident, &masked);
+    let public_type = format!("{}.public", options.type_id);
+    let masked_type = format!("{}.masked", options.type_id);
+    let version = options.version;
+
+    Ok(quote! {
+        #public_struct
+        #masked_struct
+
+        impl ::poc::payload::PayloadSchema for #public_ident {
+            fn schema_type() -> ::std::borrow::Cow<'static, str> {
+                ::std::borrow::Cow::Borrowed(#public_type)
+            }
+
+            fn schema_version() -> u16 {
+                #version
+            }
+        }
+
+        impl ::poc::payload::PayloadSchema for #masked_ident {
+            fn schema_type() -> ::std::borrow::Cow<'static, str> {
+                ::std::borrow::Cow::Borrowed(#masked_type)
+            }
+
+            fn schema_version() -> u16 {
+                #version
+            }
+        }
+
+        impl ::poc::payload::StreamsPayload for #ident {
+            type Public = #public_ident;
+            type Masked = #masked_ident;
+
+            fn split(&self) -> (Self::Public, Self::Masked) {
+                (
+                    #public_ident {
+                        #(#p_fields: ::std::clone::Clone::clone(&self.#p_fields),)*
+                    },
+                    #masked_ident {
+                        #(#m_fields: ::std::clone::Clone::clone(&self.#m_fields),)*
+                    },
+                )
+            }
+
+            fn join(public: Self::Public, masked: Self::Masked) -> Self {
+                #ident {
+                    #(#p_fields: public.#p_fields,)*
+                    #(#m_fields: masked.#m_fields,)*
+                }
+            }
+        }
+    })
+}
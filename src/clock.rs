@@ -0,0 +1,299 @@
+//!
+//! Clock Module
+//!
+//! Source of the timestamps of the readings and the payload envelopes. [`SystemClock`] reads the
+//! system time, [`FakeClock`] returns fixed timestamps so test data is reproducible.
+//!
+//! Subscribers compare the timestamps of the readings with their receipt time using
+//! [`SkewBounds`], to find devices with drifting clocks.
+//!
+use chrono::{DateTime, Duration, NaiveDateTime, SecondsFormat, TimeZone, Utc};
+use std::sync::{
+    atomic::{AtomicI64, Ordering},
+    Arc,
+};
+
+///
+/// Source of timestamps
+///
+pub trait Clock {
+    ///
+    /// Current time
+    ///
+    fn now(&self) -> DateTime<Utc>;
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> DateTime<Utc> {
+        (**self).now()
+    }
+}
+
+///
+/// System time
+///
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc::now()
+    }
+}
+
+///
+/// Clock starting at a fixed time and moving `step` forward on every reading
+///
+#[derive(Debug)]
+pub struct FakeClock {
+    next: AtomicI64,
+    step: i64,
+}
+
+impl FakeClock {
+    ///
+    /// Create Instance
+    ///
+    pub fn new(start: DateTime<Utc>, step: Duration) -> Self {
+        FakeClock {
+            next: AtomicI64::new(to_nanos(start)),
+            step: step.num_nanoseconds().unwrap_or(i64::MAX),
+        }
+    }
+
+    ///
+    /// Clock stopped at the time
+    ///
+    pub fn fixed(time: DateTime<Utc>) -> Self {
+        Self::new(time, Duration::zero())
+    }
+
+    ///
+    /// Set the time returned by the next reading
+    ///
+    pub fn set(&self, time: DateTime<Utc>) {
+        self.next.store(to_nanos(time), Ordering::SeqCst);
+    }
+
+    ///
+    /// Move the clock forward
+    ///
+    pub fn advance(&self, duration: Duration) {
+        self.next.fetch_add(
+            duration.num_nanoseconds().unwrap_or(i64::MAX),
+            Ordering::SeqCst,
+        );
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> DateTime<Utc> {
+        Utc.timestamp_nanos(self.next.fetch_add(self.step, Ordering::SeqCst))
+    }
+}
+
+fn to_nanos(time: DateTime<Utc>) -> i64 {
+    time.timestamp_nanos_opt().unwrap_or(i64::MAX)
+}
+
+///
+/// Data with the time it was taken
+///
+pub trait Timestamped {
+    ///
+    /// Time the data was taken
+    ///
+    fn time(&self) -> DateTime<Utc>;
+}
+
+///
+/// Difference between the time of a reading and its receipt time
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Skew {
+    /// The skew is within the bounds, positive when the reading is older than its receipt
+    InBounds(Duration),
+    /// The reading is timestamped after its receipt by more than the bounds
+    Ahead(Duration),
+    /// The reading is timestamped before its receipt by more than the bounds
+    Behind(Duration),
+}
+
+impl Skew {
+    ///
+    /// Check if the skew is out of the bounds
+    ///
+    pub fn is_out_of_bounds(&self) -> bool {
+        !matches!(self, Skew::InBounds(_))
+    }
+}
+
+///
+/// Maximum skew between the time of a reading and its receipt time
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SkewBounds {
+    /// Maximum time a reading can be timestamped after its receipt
+    pub ahead: Duration,
+    /// Maximum time a reading can be timestamped before its receipt
+    pub behind: Duration,
+}
+
+impl Default for SkewBounds {
+    ///
+    /// Readings up to 1 minute ahead and 1 hour behind
+    ///
+    fn default() -> Self {
+        SkewBounds {
+            ahead: Duration::minutes(1),
+            behind: Duration::hours(1),
+        }
+    }
+}
+
+impl SkewBounds {
+    ///
+    /// Compare the time of the reading with its receipt time
+    ///
+    pub fn check(&self, time: DateTime<Utc>, received: DateTime<Utc>) -> Skew {
+        let skew = received - time;
+        if -skew > self.ahead {
+            Skew::Ahead(-skew)
+        } else if skew > self.behind {
+            Skew::Behind(skew)
+        } else {
+            Skew::InBounds(skew)
+        }
+    }
+
+    ///
+    /// Compare the time of the data with its receipt time
+    ///
+    pub fn check_data<T: Timestamped + ?Sized>(&self, data: &T, received: DateTime<Utc>) -> Skew {
+        self.check(data.time(), received)
+    }
+}
+
+///
+/// RFC 3339 timestamps in UTC with nanoseconds, for `#[serde(with = "crate::clock::rfc3339")]`
+///
+/// Timestamps without offset are read as UTC
+///
+pub mod rfc3339 {
+    use chrono::{DateTime, Utc};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    ///
+    /// Write the timestamp
+    ///
+    pub fn serialize<S: Serializer>(
+        time: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::format(time))
+    }
+
+    ///
+    /// Read the timestamp
+    ///
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        super::parse(&value).map_err(de::Error::custom)
+    }
+}
+
+///
+/// Parse a RFC 3339 timestamp, timestamps without offset are read as UTC
+///
+pub fn parse(value: &str) -> anyhow::Result<DateTime<Utc>> {
+    match DateTime::parse_from_rfc3339(value) {
+        Ok(time) => Ok(time.with_timezone(&Utc)),
+        Err(_) => value
+            .parse::<NaiveDateTime>()
+            .map(|time| Utc.from_utc_datetime(&time))
+            .map_err(|e| anyhow::anyhow!("Invalid timestamp {}: {}", value, e)),
+    }
+}
+
+///
+/// Format a timestamp as RFC 3339 in UTC with nanoseconds
+///
+pub fn format(time: &DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format, parse, Clock, FakeClock, Skew, SkewBounds};
+    use crate::{
+        payload::{json, PacketPayload},
+        sample::StreamsData,
+    };
+    use chrono::{Duration, TimeZone, Utc};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use std::sync::Arc;
+
+    #[test]
+    fn fake_clock_steps() {
+        let start = Utc.with_ymd_and_hms(2021, 4, 1, 18, 0, 0).unwrap();
+        let clock = FakeClock::new(start, Duration::seconds(10));
+        assert_eq!(clock.now(), start);
+        assert_eq!(clock.now(), start + Duration::seconds(10));
+
+        clock.advance(Duration::minutes(1));
+        assert_eq!(clock.now(), start + Duration::seconds(80));
+        clock.set(start);
+        assert_eq!(clock.now(), start);
+    }
+
+    #[test]
+    fn skew_bounds() {
+        let received = Utc.with_ymd_and_hms(2021, 4, 1, 18, 0, 0).unwrap();
+        let bounds = SkewBounds::default();
+        assert_eq!(
+            bounds.check(received - Duration::seconds(5), received),
+            Skew::InBounds(Duration::seconds(5))
+        );
+        assert_eq!(
+            bounds.check(received + Duration::minutes(5), received),
+            Skew::Ahead(Duration::minutes(5))
+        );
+        assert!(bounds
+            .check(received - Duration::hours(2), received)
+            .is_out_of_bounds());
+    }
+
+    #[test]
+    fn rfc3339_timestamps() {
+        let time = parse("2021-04-01T18:19:49.123456789+02:00").unwrap();
+        assert_eq!(format(&time), "2021-04-01T16:19:49.123456789Z");
+        assert_eq!(parse("2021-04-01T16:19:49.123456789").unwrap(), time);
+        assert!(parse("yesterday").is_err());
+    }
+
+    #[test]
+    fn reproducible_payloads() {
+        let build = || {
+            let clock = Arc::new(FakeClock::fixed(
+                Utc.timestamp_opt(1_617_300_000, 0).unwrap(),
+            ));
+            let mut rng = ChaCha8Rng::seed_from_u64(42);
+            let data = StreamsData::random(&mut rng, &clock);
+            json::PayloadBuilder::new()
+                .clock(clock)
+                .public(&data)
+                .unwrap()
+                .build()
+        };
+        assert_eq!(build().public_data().0, build().public_data().0);
+    }
+}
@@ -0,0 +1,474 @@
+//!
+//! Keystore Module
+//!
+//! Channel seeds stored by identity name in a password encrypted file, so the authors and the
+//! subscribers keep their channels across restarts without passing the seed on the command line.
+//!
+//! The key of the file is derived from the password with Argon2id. Every seed is encrypted with
+//! XChaCha20-Poly1305 using the identity name as associated data, so the seeds can't be swapped
+//! between identities. The `check` entry is encrypted when the file is created and tells a wrong
+//! password apart from a keystore without identities, its associated data can't be the one of an
+//! identity.
+//!
+//! The file is written to a temporary file readable by the owner only, which then replaces the
+//! previous file, see [`write_private`].
+//!
+//! ```json
+//! {
+//!   "version": 1,
+//!   "kdf": { "salt": "<base64>", "m_cost": 19456, "t_cost": 2, "p_cost": 1 },
+//!   "check": { "nonce": "<base64>", "ciphertext": "<base64>" },
+//!   "identities": {
+//!     "<NAME>": { "nonce": "<base64>", "ciphertext": "<base64>" }
+//!   }
+//! }
+//! ```
+//!
+use crate::seed::{self, Seed};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload as AeadPayload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use rand::{rngs::OsRng, Rng};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fmt, fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+use zeroize::Zeroizing;
+
+///
+/// Version of the keystore file format
+///
+pub const VERSION: u32 = 1;
+
+///
+/// Environment variable with the keystore password, see [`password`]
+///
+pub const PASSWORD_ENV: &str = "POC_KEYSTORE_PASSWORD";
+
+const SALT_LEN: usize = 16;
+
+const NONCE_LEN: usize = 24;
+
+const CHECK: &[u8] = b"poc-keystore";
+
+const CHECK_AAD: &[u8] = b"check";
+
+const IDENTITY_AAD: &[u8] = b"identity:";
+
+///
+/// Argon2id cost parameters
+///
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct KdfParams {
+    /// Memory size in KiB
+    pub m_cost: u32,
+    /// Number of iterations
+    pub t_cost: u32,
+    /// Degree of parallelism
+    pub p_cost: u32,
+}
+
+impl Default for KdfParams {
+    ///
+    /// The Argon2id parameters recommended by OWASP: 19 MiB, 2 iterations, 1 lane
+    ///
+    fn default() -> Self {
+        KdfParams {
+            m_cost: Params::DEFAULT_M_COST,
+            t_cost: Params::DEFAULT_T_COST,
+            p_cost: Params::DEFAULT_P_COST,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct Kdf {
+    salt: String,
+    #[serde(flatten)]
+    params: KdfParams,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct Sealed {
+    nonce: String,
+    ciphertext: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeystoreFile {
+    version: u32,
+    kdf: Kdf,
+    check: Sealed,
+    identities: BTreeMap<String, Sealed>,
+}
+
+///
+/// Named seeds unlocked with the password of the keystore
+///
+pub struct Keystore {
+    salt: [u8; SALT_LEN],
+    params: KdfParams,
+    cipher: XChaCha20Poly1305,
+    check: Sealed,
+    identities: BTreeMap<String, Sealed>,
+}
+
+impl fmt::Debug for Keystore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keystore")
+            .field("params", &self.params)
+            .field("identities", &self.identities.keys().collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl Keystore {
+    ///
+    /// Create an empty keystore with the default Argon2id parameters
+    ///
+    pub fn create(password: &str) -> anyhow::Result<Self> {
+        Self::create_with(password, KdfParams::default())
+    }
+
+    ///
+    /// Create an empty keystore with the Argon2id parameters
+    ///
+    pub fn create_with(password: &str, params: KdfParams) -> anyhow::Result<Self> {
+        let salt: [u8; SALT_LEN] = OsRng.gen();
+        let cipher = derive_cipher(password, &salt, &params)?;
+        let check = seal(&cipher, CHECK_AAD, CHECK)?;
+        Ok(Keystore {
+            salt,
+            params,
+            cipher,
+            check,
+            identities: BTreeMap::new(),
+        })
+    }
+
+    ///
+    /// Read the keystore file and unlock it with the password
+    ///
+    pub fn open<P: AsRef<Path>>(path: P, password: &str) -> anyhow::Result<Self> {
+        let raw = fs::read(path)?;
+        let file: KeystoreFile =
+            serde_json::from_slice(&raw).map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+        if file.version != VERSION {
+            return Err(anyhow::anyhow!(
+                "Unsupported keystore version {}",
+                file.version
+            ));
+        }
+
+        let mut salt = [0u8; SALT_LEN];
+        decode_to(&file.kdf.salt, &mut salt)?;
+        let cipher = derive_cipher(password, &salt, &file.kdf.params)?;
+        let check = open(&cipher, CHECK_AAD, &file.check);
+        if !matches!(check, Ok(check) if check.as_slice() == CHECK) {
+            return Err(anyhow::anyhow!("Wrong keystore password"));
+        }
+
+        Ok(Keystore {
+            salt,
+            params: file.kdf.params,
+            cipher,
+            check: file.check,
+            identities: file.identities,
+        })
+    }
+
+    ///
+    /// Open the keystore file, or create an empty keystore if the file does not exist
+    ///
+    pub fn open_or_create<P: AsRef<Path>>(path: P, password: &str) -> anyhow::Result<Self> {
+        if path.as_ref().exists() {
+            Self::open(path, password)
+        } else {
+            Self::create(password)
+        }
+    }
+
+    ///
+    /// Write the keystore file
+    ///
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let file = KeystoreFile {
+            version: VERSION,
+            kdf: Kdf {
+                salt: base64::encode(self.salt),
+                params: self.params,
+            },
+            check: self.check.clone(),
+            identities: self.identities.clone(),
+        };
+        let raw = serde_json::to_vec_pretty(&file).map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+        write_private(path, &raw)
+    }
+
+    ///
+    /// Names of the identities
+    ///
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.identities.keys().map(String::as_str)
+    }
+
+    ///
+    /// Check if the keystore has the identity
+    ///
+    pub fn contains(&self, name: &str) -> bool {
+        self.identities.contains_key(name)
+    }
+
+    ///
+    /// Add the seed of an identity, replacing the seed with the same name
+    ///
+    pub fn insert(&mut self, name: &str, seed: &Seed) -> anyhow::Result<&mut Self> {
+        let sealed = seal(&self.cipher, &identity_aad(name), seed.as_str().as_bytes())?;
+        self.identities.insert(name.to_string(), sealed);
+        Ok(self)
+    }
+
+    ///
+    /// Seed of the identity
+    ///
+    pub fn get(&self, name: &str) -> anyhow::Result<Option<Seed>> {
+        match self.identities.get(name) {
+            Some(sealed) => {
+                let seed = open(&self.cipher, &identity_aad(name), sealed)
+                    .map_err(|_| anyhow::anyhow!("The seed of {} can't be decrypted", name))?;
+                std::str::from_utf8(&seed)
+                    .map(|seed| Some(Seed::from(seed)))
+                    .map_err(|e| anyhow::anyhow!("{:#?}", e))
+            }
+            None => Ok(None),
+        }
+    }
+
+    ///
+    /// Seed of the identity, the identity is added with a random seed if it does not exist
+    ///
+    pub fn get_or_generate(&mut self, name: &str) -> anyhow::Result<Seed> {
+        if let Some(seed) = self.get(name)? {
+            return Ok(seed);
+        }
+        let seed = seed::generate();
+        self.insert(name, &seed)?;
+        Ok(seed)
+    }
+
+    ///
+    /// Remove an identity, `true` if it existed
+    ///
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.identities.remove(name).is_some()
+    }
+}
+
+///
+/// Keystore password from the `POC_KEYSTORE_PASSWORD` environment variable, or asked on the
+/// terminal
+///
+pub fn password() -> anyhow::Result<Zeroizing<String>> {
+    match std::env::var(PASSWORD_ENV) {
+        Ok(password) => Ok(Zeroizing::new(password)),
+        Err(_) => Ok(Zeroizing::new(rpassword::read_password_from_tty(Some(
+            "Keystore password: ",
+        ))?)),
+    }
+}
+
+///
+/// Seed of the identity in the keystore file, unlocked with [`password`]. The file is created
+/// and the identity gets a random seed the first time
+///
+pub fn load_or_generate_seed<P: AsRef<Path>>(path: P, name: &str) -> anyhow::Result<Seed> {
+    let path = path.as_ref();
+    let mut keystore = Keystore::open_or_create(path, &password()?)?;
+    if let Some(seed) = keystore.get(name)? {
+        return Ok(seed);
+    }
+    let seed = seed::generate();
+    keystore.insert(name, &seed)?;
+    keystore.save(path)?;
+    Ok(seed)
+}
+
+///
+/// Write the file readable by the owner only
+///
+/// The data is written and synced to a temporary file next to the file, which is then renamed
+/// over the file, so the previous file stays whole if the write fails
+///
+pub fn write_private<P: AsRef<Path>>(path: P, data: &[u8]) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    let mut tmp = path.as_os_str().to_owned();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    // A temporary file left by a failed write may have any permissions
+    let _ = fs::remove_file(&tmp);
+    let written = options
+        .open(&tmp)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&tmp, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    written.map_err(|e| anyhow::anyhow!("{:#?}", e))
+}
+
+fn identity_aad(name: &str) -> Vec<u8> {
+    [IDENTITY_AAD, name.as_bytes()].concat()
+}
+
+fn derive_cipher(
+    password: &str,
+    salt: &[u8],
+    params: &KdfParams,
+) -> anyhow::Result<XChaCha20Poly1305> {
+    let params = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(32))
+        .map_err(|e| anyhow::anyhow!("Invalid Argon2id parameters: {}", e))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(password.as_bytes(), salt, &mut *key)
+        .map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+    Ok(XChaCha20Poly1305::new(Key::from_slice(&*key)))
+}
+
+fn seal(cipher: &XChaCha20Poly1305, aad: &[u8], data: &[u8]) -> anyhow::Result<Sealed> {
+    let nonce: [u8; NONCE_LEN] = OsRng.gen();
+    let ciphertext = cipher
+        .encrypt(XNonce::from_slice(&nonce), AeadPayload { msg: data, aad })
+        .map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+    Ok(Sealed {
+        nonce: base64::encode(nonce),
+        ciphertext: base64::encode(ciphertext),
+    })
+}
+
+fn open(
+    cipher: &XChaCha20Poly1305,
+    aad: &[u8],
+    sealed: &Sealed,
+) -> anyhow::Result<Zeroizing<Vec<u8>>> {
+    let mut nonce = [0u8; NONCE_LEN];
+    decode_to(&sealed.nonce, &mut nonce)?;
+    let ciphertext = base64::decode(&sealed.ciphertext).map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+    cipher
+        .decrypt(
+            XNonce::from_slice(&nonce),
+            AeadPayload {
+                msg: &ciphertext,
+                aad,
+            },
+        )
+        .map(Zeroizing::new)
+        .map_err(|e| anyhow::anyhow!("{:#?}", e))
+}
+
+fn decode_to(value: &str, out: &mut [u8]) -> anyhow::Result<()> {
+    let bytes = base64::decode(value).map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+    if bytes.len() != out.len() {
+        return Err(anyhow::anyhow!(
+            "Expected {} bytes, found {}",
+            out.len(),
+            bytes.len()
+        ));
+    }
+    out.copy_from_slice(&bytes);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KdfParams, Keystore};
+    use crate::seed::{self, Seed};
+
+    fn fast() -> KdfParams {
+        KdfParams {
+            m_cost: 64,
+            t_cost: 1,
+            p_cost: 1,
+        }
+    }
+
+    #[test]
+    fn keystore_roundtrip() {
+        let path = std::env::temp_dir().join(format!("poc-keystore-{}.json", std::process::id()));
+        let author = seed::generate();
+
+        let mut keystore = Keystore::create_with("secret", fast()).unwrap();
+        keystore.insert("author", &author).unwrap();
+        let subscriber = keystore.get_or_generate("subscriber").unwrap();
+        keystore.save(&path).unwrap();
+
+        let raw = std::fs::read_to_string(&path).unwrap();
+        assert!(!raw.contains(author.as_str()));
+        assert!(!raw.contains(subscriber.as_str()));
+
+        let mut keystore = Keystore::open(&path, "secret").unwrap();
+        assert_eq!(
+            keystore.names().collect::<Vec<_>>(),
+            vec!["author", "subscriber"]
+        );
+        assert_eq!(keystore.get("author").unwrap().unwrap(), author);
+        assert_eq!(keystore.get_or_generate("subscriber").unwrap(), subscriber);
+        assert!(keystore.get("gateway").unwrap().is_none());
+        assert!(keystore.remove("author"));
+        assert!(!keystore.contains("author"));
+
+        let wrong = Keystore::open(&path, "guess").unwrap_err();
+        assert_eq!(wrong.to_string(), "Wrong keystore password");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn seeds_bound_to_names() {
+        let mut keystore = Keystore::create_with("secret", fast()).unwrap();
+        keystore
+            .insert("author", &Seed::from("AUTHORSEED"))
+            .unwrap();
+        let sealed = keystore.identities["author"].clone();
+        keystore.identities.insert("thief".to_string(), sealed);
+
+        assert!(keystore.get("thief").is_err());
+        assert!(!format!("{:?}", keystore).contains("AUTHORSEED"));
+
+        // The check entry is not the seed of any identity
+        let check = keystore.check.clone();
+        keystore.identities.insert(String::new(), check);
+        assert!(keystore.get("").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn private_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let path = std::env::temp_dir().join(format!("poc-private-{}.json", std::process::id()));
+        std::fs::write(&path, "previous").unwrap();
+        let keystore = Keystore::create_with("secret", fast()).unwrap();
+        keystore.save(&path).unwrap();
+
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert!(Keystore::open(&path, "secret").is_ok());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
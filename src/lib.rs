@@ -1,24 +1,48 @@
 //!
 //! PoC Lib
 //!
+
+// Lets the derive macros refer to `::poc` inside this crate
+extern crate self as poc;
+
+pub mod clock;
+pub mod keystore;
 pub mod payload;
+pub mod seed;
 pub mod transport;
 
 pub mod sample {
-    use crate::payload::json::Payload;
-    use chrono::{Local, NaiveDateTime};
+    use crate::clock::{Clock, SkewBounds, SystemClock, Timestamped};
+    #[cfg(feature = "protobuf")]
+    use crate::payload::protobuf::ProtobufSerializer;
+    use crate::payload::{
+        json::JsonSerializer,
+        registry::{Decoded, DecoderRegistry},
+        senml::{SenmlJsonSerializer, SenmlPack, SenmlRecord},
+        timeseries::{Series, SeriesRecord},
+        validation::{JsonSchema, SchemaSet},
+        PayloadError, PayloadSchema,
+    };
+    use chrono::{DateTime, TimeZone, Utc};
     use crypto::hashes::{blake2b, Digest};
     use iota_streams::{app_channels::api::tangle::Address, ddml::types::Bytes};
     use rand::{Rng, distributions::Uniform};
     use serde::{Deserialize, Serialize};
+    use std::{borrow::Cow, convert::TryFrom};
+
+    pub mod measurement;
+    pub mod source;
+    pub use measurement::Measurement;
+    pub use source::{open_source, MeasurementSource};
 
     ///
     /// Simple Streams Data
     ///
-    #[derive(Debug, Serialize, Deserialize)]
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
     pub struct StreamsData {
-        /// Current Timestap
-        ts: NaiveDateTime,
+        /// Current Timestap, UTC with nanoseconds
+        #[serde(with = "crate::clock::rfc3339")]
+        ts: DateTime<Utc>,
         /// Sample description
         desc: String,
         /// Temperature
@@ -29,71 +53,368 @@ pub mod sample {
 
     impl StreamsData {
         pub fn new<S>(desc: S, temp: f32, pressure: f32) -> Self
+        where
+            S: Into<String>,
+        {
+            Self::new_at(SystemClock.now(), desc, temp, pressure)
+        }
+
+        ///
+        /// Create the reading taken at the timestamp
+        ///
+        pub fn new_at<S>(ts: DateTime<Utc>, desc: S, temp: f32, pressure: f32) -> Self
         where
             S: Into<String>,
         {
             StreamsData {
-                ts: Local::now().naive_utc(),
+                ts,
                 desc: desc.into(),
                 temperature: temp,
                 pressure: pressure,
             }
         }
-    }
 
-    impl Default for StreamsData {
-        fn default() -> Self {
-            let mut rng = rand::thread_rng();
-            StreamsData::new(
-                make_random_data(rng.gen_range(10, 50)),
+        ///
+        /// Generate a random reading with the RNG, timestamped by the clock
+        ///
+        pub fn random<R, C>(rng: &mut R, clock: &C) -> Self
+        where
+            R: Rng + ?Sized,
+            C: Clock + ?Sized,
+        {
+            let len = rng.gen_range(10, 50);
+            Self::new_at(
+                clock.now(),
+                make_random_data_with(rng, len),
                 rng.gen_range(-10.0, 1.3e3),
                 rng.gen_range(10.0, 1.3e5),
             )
         }
+
+        ///
+        /// Sample timestamp
+        ///
+        pub fn ts(&self) -> &DateTime<Utc> {
+            &self.ts
+        }
+    }
+
+    impl Timestamped for StreamsData {
+        fn time(&self) -> DateTime<Utc> {
+            self.ts
+        }
+    }
+
+    impl PayloadSchema for StreamsData {
+        fn schema_type() -> Cow<'static, str> {
+            Cow::Borrowed("streams-data")
+        }
+    }
+
+    impl SeriesRecord for StreamsData {
+        const VALUES: usize = 2;
+
+        fn timestamp(&self) -> anyhow::Result<i64> {
+            self.ts.timestamp_nanos_opt().ok_or_else(|| {
+                anyhow::anyhow!("Timestamp {} out of the nanoseconds range", self.ts)
+            })
+        }
+
+        fn label(&self) -> &str {
+            &self.desc
+        }
+
+        fn values(&self) -> Vec<f64> {
+            vec![self.temperature as f64, self.pressure as f64]
+        }
+
+        fn from_series(timestamp: i64, label: String, values: &[f64]) -> Self {
+            StreamsData {
+                ts: Utc.timestamp_nanos(timestamp),
+                desc: label,
+                temperature: values[0] as f32,
+                pressure: values[1] as f32,
+            }
+        }
+    }
+
+    ///
+    /// Protobuf messages generated from `proto/streams_data.proto`
+    ///
+    #[cfg(feature = "protobuf")]
+    pub mod proto {
+        include!(concat!(env!("OUT_DIR"), "/poc.sample.rs"));
+    }
+
+    #[cfg(feature = "protobuf")]
+    impl PayloadSchema for proto::StreamsData {
+        fn schema_type() -> Cow<'static, str> {
+            StreamsData::schema_type()
+        }
+    }
+
+    #[cfg(feature = "protobuf")]
+    impl TryFrom<&StreamsData> for proto::StreamsData {
+        type Error = anyhow::Error;
+
+        fn try_from(data: &StreamsData) -> anyhow::Result<Self> {
+            Ok(proto::StreamsData {
+                ts: data.timestamp()?,
+                desc: data.desc.clone(),
+                temperature: data.temperature,
+                pressure: data.pressure,
+            })
+        }
+    }
+
+    #[cfg(feature = "protobuf")]
+    impl From<proto::StreamsData> for StreamsData {
+        fn from(message: proto::StreamsData) -> Self {
+            StreamsData {
+                ts: Utc.timestamp_nanos(message.ts),
+                desc: message.desc,
+                temperature: message.temperature,
+                pressure: message.pressure,
+            }
+        }
+    }
+
+    impl From<&StreamsData> for SenmlPack {
+        fn from(data: &StreamsData) -> Self {
+            SenmlPack::new(vec![
+                SenmlRecord {
+                    base_name: Some(SENML_BASE_NAME.to_string()),
+                    base_time: Some(
+                        data.ts.timestamp() as f64 + data.ts.timestamp_subsec_nanos() as f64 / 1e9,
+                    ),
+                    name: Some("temperature".to_string()),
+                    unit: Some("Cel".to_string()),
+                    value: Some(data.temperature as f64),
+                    ..Default::default()
+                },
+                SenmlRecord {
+                    name: Some("pressure".to_string()),
+                    unit: Some("Pa".to_string()),
+                    value: Some(data.pressure as f64),
+                    ..Default::default()
+                },
+                SenmlRecord {
+                    name: Some("desc".to_string()),
+                    string_value: Some(data.desc.clone()),
+                    ..Default::default()
+                },
+            ])
+        }
+    }
+
+    impl TryFrom<&SenmlPack> for StreamsData {
+        type Error = anyhow::Error;
+
+        fn try_from(pack: &SenmlPack) -> anyhow::Result<Self> {
+            let record = |name: &str| {
+                pack.find(&format!("{}{}", SENML_BASE_NAME, name))
+                    .ok_or_else(|| anyhow::anyhow!("Missing SenML record {}", name))
+            };
+            let number = |name: &str| {
+                record(name)?
+                    .value
+                    .ok_or_else(|| anyhow::anyhow!("Missing SenML value of {}", name))
+            };
+            let temperature = record("temperature")?;
+
+            Ok(StreamsData {
+                ts: Utc.timestamp_nanos((temperature.time.unwrap_or_default() * 1e9) as i64),
+                desc: record("desc")?.string_value.unwrap_or_default(),
+                temperature: number("temperature")? as f32,
+                pressure: number("pressure")? as f32,
+            })
+        }
+    }
+
+    ///
+    /// Base name of the SenML records of the sensor readings
+    ///
+    const SENML_BASE_NAME: &str = "streams-data:";
+
+    impl Default for StreamsData {
+        fn default() -> Self {
+            StreamsData::random(&mut rand::thread_rng(), &SystemClock)
+        }
     }
 
     ///
     /// Generate Random Data
     ///
-    pub(crate) fn make_random_data(len: usize) -> String {
+    pub fn make_random_data(len: usize) -> String {
+        make_random_data_with(&mut rand::thread_rng(), len)
+    }
+
+    ///
+    /// Generate Random Data with the RNG
+    ///
+    pub fn make_random_data_with<R: Rng + ?Sized>(rng: &mut R, len: usize) -> String {
         const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ\
                                 abcdefghijklmnopqrstuvwxyz\
                                 0123456789 ;,.";
-        rand::thread_rng()
-            .sample_iter(Uniform::new(0, CHARSET.len()))
-            .take(len)
-            .map(|i| CHARSET[i] as char)
+        let chars = Uniform::new(0, CHARSET.len());
+        (0..len)
+            .map(|_| CHARSET[rng.sample(chars)] as char)
             .collect()
     }
     ///
-    /// Generate Random Seed
+    /// Generate Random Seed from the OS random number generator, see [`seed`](crate::seed)
     ///
-    pub fn make_random_seed() -> String {
-        const CHARSET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ9";
-        rand::thread_rng()
-            .sample_iter(Uniform::new(0, CHARSET.len()))
-            .take(81)
-            .map(|i| CHARSET[i] as char)
-            .collect()
+    pub fn make_random_seed() -> crate::seed::Seed {
+        crate::seed::generate()
+    }
+
+    ///
+    /// Generate Random Seed with the RNG
+    ///
+    pub fn make_random_seed_with<R: Rng + ?Sized>(rng: &mut R) -> crate::seed::Seed {
+        crate::seed::generate_with(rng)
+    }
+
+    ///
+    /// Messages published by the examples
+    ///
+    #[derive(Debug)]
+    pub enum SampleMessage {
+        /// Sensor reading
+        StreamsData(StreamsData),
+        /// Batch of sensor readings
+        Batch(Vec<StreamsData>),
+        /// Sensor readings as a SenML pack
+        Senml(SenmlPack),
+        /// Readings of many sensors
+        Measurement(Measurement),
+    }
+
+    impl SampleMessage {
+        ///
+        /// Time of the readings of the message
+        ///
+        pub fn timestamps(&self) -> Vec<DateTime<Utc>> {
+            match self {
+                SampleMessage::StreamsData(data) => vec![data.time()],
+                SampleMessage::Batch(readings) => readings.iter().map(|r| r.time()).collect(),
+                SampleMessage::Senml(pack) => StreamsData::try_from(pack)
+                    .map(|data| vec![data.time()])
+                    .unwrap_or_default(),
+                SampleMessage::Measurement(measurement) => vec![measurement.time()],
+            }
+        }
+    }
+
+    ///
+    /// JSON Schema of the sensor readings, rejects temperatures below the absolute zero or
+    /// above the range of the sensors and negative pressures
+    ///
+    pub fn streams_data_schema() -> serde_json::Value {
+        serde_json::json!({
+            "type": "object",
+            "properties": {
+                "ts": { "type": "string" },
+                "desc": { "type": "string" },
+                "temperature": { "type": "number", "minimum": -273.15, "maximum": 1500.0 },
+                "pressure": { "type": "number", "minimum": 0.0 }
+            },
+            "required": ["ts", "desc", "temperature", "pressure"]
+        })
+    }
+
+    ///
+    /// JSON Schemas of the messages published by the examples
+    ///
+    pub fn schemas() -> SchemaSet {
+        let reading = streams_data_schema();
+        let batch = serde_json::json!({ "type": "array", "items": reading });
+        let mut schemas = SchemaSet::new();
+        schemas
+            // The schemas are valid constants
+            .insert_type("streams-data", JsonSchema::compile(&reading).unwrap())
+            .insert_type("streams-data[]", JsonSchema::compile(&batch).unwrap());
+        schemas
+    }
+
+    ///
+    /// Decoder registry of the messages published by the examples
+    ///
+    pub fn registry() -> DecoderRegistry<SampleMessage> {
+        let mut registry = DecoderRegistry::new();
+        registry
+            .register::<StreamsData, JsonSerializer, _>(SampleMessage::StreamsData)
+            .register::<Vec<StreamsData>, JsonSerializer, _>(SampleMessage::Batch)
+            .register::<Series<StreamsData>, JsonSerializer, _>(|series| {
+                SampleMessage::Batch(series.into_records())
+            })
+            .register::<SenmlPack, SenmlJsonSerializer, _>(SampleMessage::Senml)
+            .register::<Measurement, JsonSerializer, _>(SampleMessage::Measurement)
+            .validate_with(schemas());
+        #[cfg(feature = "protobuf")]
+        registry.register::<proto::StreamsData, ProtobufSerializer, _>(|message| {
+            SampleMessage::StreamsData(message.into())
+        });
+        registry
     }
 
     /// Print message payload
     ///
-    pub fn print_message_payload<T>(prefix: T, public: &Bytes, masked: &Bytes)
-    where
+    /// Decode failures are printed to stderr instead of aborting, as well as the readings with
+    /// a timestamp out of the default [`SkewBounds`] from now
+    ///
+    pub fn print_message_payload<T>(
+        registry: &DecoderRegistry<SampleMessage>,
+        prefix: T,
+        public: &Bytes,
+        masked: &Bytes,
+    ) where
         T: Into<String>,
     {
-        let p_data: Option<StreamsData> = Payload::unwrap_data(&public).unwrap();
-        let m_data: Option<StreamsData> = Payload::unwrap_data(&masked).unwrap();
         let pfx = prefix.into();
+        let received = SystemClock.now();
+
+        let public = registry.decode(public);
+        print_skew(&pfx, "Public", &public, received);
+        print_decoded(&pfx, "Public", public);
+        let masked = registry.decode(masked);
+        print_skew(&pfx, "Masked", &masked, received);
+        print_decoded(&pfx, "Masked", masked);
+    }
 
-        match p_data {
-            Some(d) => println!("\n {} Public Packet: \n \t{:?}\n", pfx, d),
-            None => {}
+    fn print_skew(
+        prefix: &str,
+        kind: &str,
+        data: &Result<Decoded<SampleMessage>, PayloadError>,
+        received: DateTime<Utc>,
+    ) {
+        let bounds = SkewBounds::default();
+        for time in data.iter().flat_map(|decoded| decoded.message.timestamps()) {
+            let skew = bounds.check(time, received);
+            if skew.is_out_of_bounds() {
+                eprintln!(
+                    "\n {} {} Packet: \n \tClock skew out of bounds {:?} at {}\n",
+                    prefix, kind, skew, time
+                );
+            }
         }
-        match m_data {
-            Some(d) => println!("\n {} Masked Packet: \n \t{:?}\n", pfx, d),
-            None => {}
+    }
+
+    /// Print decoded message
+    ///
+    pub fn print_decoded<M>(prefix: &str, kind: &str, data: Result<Decoded<M>, PayloadError>)
+    where
+        M: std::fmt::Debug,
+    {
+        match data {
+            Ok(Decoded {
+                envelope, message, ..
+            }) => println!(
+                "\n {} {} Packet ({} v{}): \n \t{:?}\n",
+                prefix, kind, envelope.type_id, envelope.version, message
+            ),
+            Err(PayloadError::Empty) => {}
+            Err(e) => eprintln!("\n {} {} Packet: \n \t{}\n", prefix, kind, e),
         }
     }
 
@@ -0,0 +1,187 @@
+//!
+//! Batch Payload module
+//!
+//! Accumulates readings and packs as many as fit in the size budget into one packet. The
+//! readings are sent as a list, so every reading keeps its own timestamp.
+//!
+use super::{Payload, PayloadBuilder, PayloadError, PayloadSchema, PayloadSerializer};
+use iota_streams::ddml::types::Bytes;
+use serde::{de::DeserializeOwned, Serialize};
+
+///
+/// Batch of readings waiting to be sent
+///
+#[derive(Debug, Clone)]
+pub struct BatchPayload<T> {
+    readings: Vec<T>,
+}
+
+impl<T> Default for BatchPayload<T> {
+    fn default() -> Self {
+        BatchPayload {
+            readings: Vec::new(),
+        }
+    }
+}
+
+impl<T> BatchPayload<T>
+where
+    T: Serialize + PayloadSchema,
+{
+    ///
+    /// Create Instance
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Add a reading to the batch
+    ///
+    pub fn push(&mut self, reading: T) -> &mut Self {
+        self.readings.push(reading);
+        self
+    }
+
+    ///
+    /// Number of readings waiting to be sent
+    ///
+    pub fn len(&self) -> usize {
+        self.readings.len()
+    }
+
+    ///
+    /// Check if there are no readings waiting to be sent
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.readings.is_empty()
+    }
+
+    ///
+    /// Pack the oldest readings which fit in `limit` bytes as public data
+    ///
+    /// Returns `None` when the batch is empty. The packed readings are removed from the batch
+    ///
+    pub fn pack_public<S>(&mut self, limit: usize) -> Result<Option<Payload<S>>, PayloadError>
+    where
+        S: PayloadSerializer,
+    {
+        self.pack(limit, |builder, readings| builder.public(readings))
+    }
+
+    ///
+    /// Pack the oldest readings which fit in `limit` bytes as masked data
+    ///
+    /// Returns `None` when the batch is empty. The packed readings are removed from the batch
+    ///
+    pub fn pack_masked<S>(&mut self, limit: usize) -> Result<Option<Payload<S>>, PayloadError>
+    where
+        S: PayloadSerializer,
+    {
+        self.pack(limit, |builder, readings| builder.masked(readings))
+    }
+
+    fn pack<S, F>(&mut self, limit: usize, set: F) -> Result<Option<Payload<S>>, PayloadError>
+    where
+        S: PayloadSerializer,
+        F: for<'b> Fn(
+            &'b mut PayloadBuilder<S>,
+            &Vec<&T>,
+        ) -> anyhow::Result<&'b mut PayloadBuilder<S>>,
+    {
+        if self.readings.is_empty() {
+            return Ok(None);
+        }
+
+        let build = |count: usize| -> Result<Payload<S>, PayloadError> {
+            let readings: Vec<&T> = self.readings[..count].iter().collect();
+            let mut builder = PayloadBuilder::<S>::new();
+            builder.limit(limit);
+            set(&mut builder, &readings).map_err(PayloadError::Serialize)?;
+            builder.try_build()
+        };
+
+        // Largest number of readings which fits, the first one must always fit
+        let mut packed = build(1)?;
+        let (mut fits, mut exceeds) = (1, self.readings.len() + 1);
+        while exceeds - fits > 1 {
+            let count = (fits + exceeds) / 2;
+            match build(count) {
+                Ok(payload) => {
+                    packed = payload;
+                    fits = count;
+                }
+                Err(PayloadError::TooLarge(_)) => exceeds = count,
+                Err(e) => return Err(e),
+            }
+        }
+
+        self.readings.drain(..fits);
+        Ok(Some(packed))
+    }
+}
+
+impl<T> BatchPayload<T>
+where
+    T: DeserializeOwned + PayloadSchema,
+{
+    ///
+    /// Unpack the readings of the data, either a batch or a single reading
+    ///
+    pub fn unpack<S>(data: &Bytes) -> Result<Vec<T>, PayloadError>
+    where
+        S: PayloadSerializer,
+    {
+        let envelope = Payload::<S>::envelope(data)?;
+        if envelope.is::<T>() {
+            Ok(vec![Payload::<S>::unwrap_data::<T>(data)?])
+        } else {
+            Payload::<S>::unwrap_data::<Vec<T>>(data)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BatchPayload;
+    use crate::{
+        payload::{json::JsonSerializer, PacketPayload, PayloadError},
+        sample::StreamsData,
+    };
+
+    #[test]
+    fn pack_readings_under_limit() {
+        let readings: Vec<StreamsData> = (0..30).map(|_| StreamsData::default()).collect();
+        let mut batch = BatchPayload::new();
+        for reading in readings.iter().cloned() {
+            batch.push(reading);
+        }
+
+        let mut unpacked = Vec::new();
+        let mut packets = 0;
+        while let Some(payload) = batch.pack_masked::<JsonSerializer>(1024).unwrap() {
+            assert!(payload.masked_data().0.len() <= 1024);
+            unpacked.extend(
+                BatchPayload::<StreamsData>::unpack::<JsonSerializer>(payload.masked_data())
+                    .unwrap(),
+            );
+            packets += 1;
+        }
+
+        assert!(batch.is_empty());
+        assert!(packets > 1 && packets < readings.len());
+        assert_eq!(unpacked, readings);
+    }
+
+    #[test]
+    fn reading_larger_than_limit() {
+        let mut batch = BatchPayload::new();
+        batch.push(StreamsData::default());
+
+        assert!(matches!(
+            batch.pack_public::<JsonSerializer>(16),
+            Err(PayloadError::TooLarge(_))
+        ));
+        assert_eq!(batch.len(), 1);
+    }
+}
@@ -0,0 +1,50 @@
+//!
+//! Bincode Payload Serialization module
+//!
+use super::PayloadSerializer;
+use serde::{de::DeserializeOwned, Serialize};
+use std::borrow::Cow;
+
+///
+/// Implementation of Bincode Serialize
+///
+/// Bincode is not self-describing, both sides must agree on the exact data type
+///
+pub struct BincodeSerializer;
+
+impl PayloadSerializer for BincodeSerializer {
+    fn content_type() -> Cow<'static, str> {
+        Cow::Borrowed("application/x-bincode")
+    }
+
+    fn serialize_data<T: Serialize>(data: &T) -> anyhow::Result<Vec<u8>> {
+        ::bincode::serialize(data).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+
+    fn deserialize_data<T: DeserializeOwned>(data: &[u8]) -> anyhow::Result<T> {
+        ::bincode::deserialize(data).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+}
+
+/// Payload Bincode
+///
+pub type Payload = super::Payload<BincodeSerializer>;
+
+/// Payload Builder in Bincode Format
+///
+pub type PayloadBuilder = super::PayloadBuilder<BincodeSerializer>;
+
+#[cfg(test)]
+mod tests {
+    use super::{Payload, PayloadBuilder};
+    use crate::{payload::PacketPayload, sample::StreamsData};
+
+    #[test]
+    fn roundtrip_streams_data() {
+        let data = StreamsData::default();
+        let payload = PayloadBuilder::new().public(&data).unwrap().build();
+
+        let public: StreamsData = Payload::unwrap_data(payload.public_data()).unwrap();
+        assert_eq!(public, data);
+    }
+}
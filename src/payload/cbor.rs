@@ -0,0 +1,48 @@
+//!
+//! CBOR Payload Serialization module
+//!
+use super::PayloadSerializer;
+use serde::{de::DeserializeOwned, Serialize};
+use std::borrow::Cow;
+
+///
+/// Implementation of CBOR Serialize
+///
+pub struct CborSerializer;
+
+impl PayloadSerializer for CborSerializer {
+    fn content_type() -> Cow<'static, str> {
+        Cow::Borrowed("application/cbor")
+    }
+
+    fn serialize_data<T: Serialize>(data: &T) -> anyhow::Result<Vec<u8>> {
+        serde_cbor::to_vec(data).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+
+    fn deserialize_data<T: DeserializeOwned>(data: &[u8]) -> anyhow::Result<T> {
+        serde_cbor::from_slice(data).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+}
+
+/// Payload CBOR
+///
+pub type Payload = super::Payload<CborSerializer>;
+
+/// Payload Builder in CBOR Format
+///
+pub type PayloadBuilder = super::PayloadBuilder<CborSerializer>;
+
+#[cfg(test)]
+mod tests {
+    use super::{Payload, PayloadBuilder};
+    use crate::{payload::PacketPayload, sample::StreamsData};
+
+    #[test]
+    fn roundtrip_streams_data() {
+        let data = StreamsData::default();
+        let payload = PayloadBuilder::new().public(&data).unwrap().build();
+
+        let public: StreamsData = Payload::unwrap_data(payload.public_data()).unwrap();
+        assert_eq!(public, data);
+    }
+}
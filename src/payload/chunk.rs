@@ -0,0 +1,490 @@
+//!
+//! Payload Chunking module
+//!
+//! Splits a payload larger than the message budget into numbered fragments, sent as
+//! linked packets, and rebuilds it on the subscriber side.
+//!
+//! Every fragment part starts with a header:
+//!
+//! | magic | index | count | total length | blake2b-256 digest |
+//! |-------|-------|-------|--------------|--------------------|
+//! | 2     | u16   | u16   | u32          | 32                 |
+//!
+//! The public and masked parts carry their own header, so the masked length and digest are
+//! never exposed in the public part. Parts longer than [`MAX_DECODED_BYTES`] or than `count`
+//! messages can carry are rejected.
+//!
+use super::{PacketPayload, PayloadError, MAX_DECODED_BYTES};
+use crypto::hashes::{blake2b, Digest};
+use iota_streams::{app::transport::tangle::PAYLOAD_BYTES, ddml::types::Bytes};
+use std::convert::TryInto;
+
+///
+/// Magic bytes of a fragment header
+///
+pub const MAGIC: [u8; 2] = [0xF7, 0x01];
+
+///
+/// Size of a fragment header
+///
+pub const HEADER_LEN: usize = 42;
+
+///
+/// Packet Fragment
+///
+#[derive(Debug, Clone)]
+pub struct Fragment {
+    public: Bytes,
+    masked: Bytes,
+}
+
+impl PacketPayload for Fragment {
+    fn public_data(&self) -> &Bytes {
+        &self.public
+    }
+    fn masked_data(&self) -> &Bytes {
+        &self.masked
+    }
+}
+
+///
+/// Fragment Header
+///
+#[derive(Debug, Clone, PartialEq)]
+struct Header {
+    index: u16,
+    count: u16,
+    total_len: u32,
+    digest: [u8; 32],
+}
+
+impl Header {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&MAGIC);
+        out.extend_from_slice(&self.index.to_be_bytes());
+        out.extend_from_slice(&self.count.to_be_bytes());
+        out.extend_from_slice(&self.total_len.to_be_bytes());
+        out.extend_from_slice(&self.digest);
+    }
+
+    fn decode(data: &[u8]) -> Option<(Self, &[u8])> {
+        if !is_fragment(data) {
+            return None;
+        }
+        let (header, body) = data.split_at(HEADER_LEN);
+        let header = Header {
+            index: u16::from_be_bytes(header[2..4].try_into().ok()?),
+            count: u16::from_be_bytes(header[4..6].try_into().ok()?),
+            total_len: u32::from_be_bytes(header[6..10].try_into().ok()?),
+            digest: header[10..42].try_into().ok()?,
+        };
+        Some((header, body))
+    }
+
+    ///
+    /// Total length of the part, checked against what `count` fragments can carry
+    ///
+    fn checked_len(&self) -> Result<usize, PayloadError> {
+        let total_len = self.total_len as usize;
+        let capacity = (self.count as usize) * (PAYLOAD_BYTES - HEADER_LEN);
+        if total_len > MAX_DECODED_BYTES || total_len > capacity {
+            return Err(PayloadError::InvalidFragment(format!(
+                "Fragmented data of {} bytes in {} fragments exceeds the limit",
+                total_len, self.count
+            )));
+        }
+        Ok(total_len)
+    }
+}
+
+///
+/// Check if the data is a fragment of a larger payload
+///
+pub fn is_fragment(data: &[u8]) -> bool {
+    data.len() >= HEADER_LEN && data[..2] == MAGIC
+}
+
+fn digest(data: &[u8]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&blake2b::Blake2b256::digest(data));
+    out
+}
+
+///
+/// Split the payload in fragments whose public and masked data together use at most
+/// `max_bytes`
+///
+/// A payload which already fits is returned as a single fragment, without headers
+///
+pub fn split<P: PacketPayload>(payload: &P, max_bytes: usize) -> anyhow::Result<Vec<Fragment>> {
+    let public = &payload.public_data().0;
+    let masked = &payload.masked_data().0;
+
+    if public.len() + masked.len() <= max_bytes {
+        return Ok(vec![Fragment {
+            public: payload.public_data().clone(),
+            masked: payload.masked_data().clone(),
+        }]);
+    }
+    if max_bytes <= 2 * HEADER_LEN {
+        return Err(anyhow::anyhow!(
+            "Fragment size {} too small, the headers use {} bytes",
+            max_bytes,
+            2 * HEADER_LEN
+        ));
+    }
+
+    let budget = max_bytes - 2 * HEADER_LEN;
+    let total = public.len() + masked.len();
+    let count = (total + budget - 1) / budget;
+    if count > u16::MAX as usize || public.len().max(masked.len()) > MAX_DECODED_BYTES {
+        return Err(anyhow::anyhow!(
+            "Payload of {} bytes needs too many fragments",
+            total
+        ));
+    }
+
+    let (p_digest, m_digest) = (digest(public), digest(masked));
+    let fragments = (0..count)
+        .map(|index| {
+            let start = index * budget;
+            let end = (start + budget).min(total);
+            let p_range = start.min(public.len())..end.min(public.len());
+            let m_range =
+                start.max(public.len()) - public.len()..end.max(public.len()) - public.len();
+
+            let mut p_part = Vec::with_capacity(HEADER_LEN + p_range.len());
+            Header {
+                index: index as u16,
+                count: count as u16,
+                total_len: public.len() as u32,
+                digest: p_digest,
+            }
+            .encode(&mut p_part);
+            p_part.extend_from_slice(&public[p_range]);
+
+            let mut m_part = Vec::with_capacity(HEADER_LEN + m_range.len());
+            Header {
+                index: index as u16,
+                count: count as u16,
+                total_len: masked.len() as u32,
+                digest: m_digest,
+            }
+            .encode(&mut m_part);
+            m_part.extend_from_slice(&masked[m_range]);
+
+            Fragment {
+                public: Bytes(p_part),
+                masked: Bytes(m_part),
+            }
+        })
+        .collect();
+
+    Ok(fragments)
+}
+
+///
+/// Fragment part being rebuilt
+///
+struct Part {
+    total_len: usize,
+    digest: [u8; 32],
+    data: Vec<u8>,
+}
+
+impl Part {
+    fn extend(&mut self, body: &[u8]) -> Result<(), PayloadError> {
+        if self.data.len() + body.len() > self.total_len {
+            return Err(PayloadError::InvalidFragment(format!(
+                "Fragments exceed the total length of {} bytes",
+                self.total_len
+            )));
+        }
+        self.data.extend_from_slice(body);
+        Ok(())
+    }
+
+    fn verify(self, name: &str) -> Result<Bytes, PayloadError> {
+        if self.data.len() != self.total_len {
+            return Err(PayloadError::InvalidFragment(format!(
+                "Reassembled {} data has {} bytes, expected {}",
+                name,
+                self.data.len(),
+                self.total_len
+            )));
+        }
+        if digest(&self.data) != self.digest {
+            return Err(PayloadError::InvalidFragment(format!(
+                "Reassembled {} data digest mismatch",
+                name
+            )));
+        }
+        Ok(Bytes(self.data))
+    }
+}
+
+struct Pending {
+    next: u16,
+    count: u16,
+    public: Part,
+    /// `None` when the masked data is not readable by this subscriber
+    masked: Option<Part>,
+}
+
+///
+/// Rebuild fragmented payloads from the messages of a channel, in sequence order
+///
+#[derive(Default)]
+pub struct Reassembler {
+    pending: Option<Pending>,
+}
+
+impl Reassembler {
+    ///
+    /// Create Instance
+    ///
+    pub fn new() -> Self {
+        Reassembler { pending: None }
+    }
+
+    ///
+    /// Check if there is an incomplete payload waiting for more fragments
+    ///
+    pub fn is_pending(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    ///
+    /// Push the public and masked data of the next message
+    ///
+    /// Returns the complete payload once the last fragment arrives. Messages which are not
+    /// fragments are returned as they are
+    ///
+    pub fn push(
+        &mut self,
+        public: &Bytes,
+        masked: &Bytes,
+    ) -> Result<Option<(Bytes, Bytes)>, PayloadError> {
+        let (p_header, p_body) = match Header::decode(&public.0) {
+            Some(decoded) => decoded,
+            None => return Ok(Some((public.clone(), masked.clone()))),
+        };
+        let masked_part = match Header::decode(&masked.0) {
+            Some((m_header, m_body)) => {
+                if m_header.index != p_header.index || m_header.count != p_header.count {
+                    self.pending = None;
+                    return Err(PayloadError::InvalidFragment(
+                        "Public and masked fragment headers differ".to_string(),
+                    ));
+                }
+                Some((m_header, m_body))
+            }
+            None => None,
+        };
+
+        if p_header.index == 0 {
+            self.pending = None;
+            let masked = match &masked_part {
+                Some((m_header, _)) => Some(Part {
+                    total_len: m_header.checked_len()?,
+                    digest: m_header.digest,
+                    data: Vec::new(),
+                }),
+                None => None,
+            };
+            self.pending = Some(Pending {
+                next: 0,
+                count: p_header.count,
+                public: Part {
+                    total_len: p_header.checked_len()?,
+                    digest: p_header.digest,
+                    data: Vec::new(),
+                },
+                masked,
+            });
+        }
+
+        let mut pending = match self.pending.take() {
+            Some(pending) if pending.next == p_header.index && pending.count == p_header.count => {
+                pending
+            }
+            _ => {
+                return Err(PayloadError::InvalidFragment(format!(
+                    "Unexpected fragment {}/{}",
+                    u32::from(p_header.index) + 1,
+                    p_header.count
+                )))
+            }
+        };
+
+        pending.public.extend(p_body)?;
+        pending.masked = match (pending.masked, masked_part) {
+            (Some(mut part), Some((_, m_body))) => {
+                part.extend(m_body)?;
+                Some(part)
+            }
+            _ => None,
+        };
+        pending.next += 1;
+
+        if pending.next < pending.count {
+            self.pending = Some(pending);
+            return Ok(None);
+        }
+
+        let public = pending.public.verify("public")?;
+        let masked = match pending.masked {
+            Some(part) => part.verify("masked")?,
+            None => Bytes(Vec::new()),
+        };
+        Ok(Some((public, masked)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_fragment, split, Header, Reassembler, HEADER_LEN};
+    use crate::{
+        payload::{
+            json::{Payload, PayloadBuilder},
+            PacketPayload, PayloadError,
+        },
+        sample::StreamsData,
+    };
+    use iota_streams::ddml::types::Bytes;
+
+    fn large_batch() -> Vec<StreamsData> {
+        (0..40).map(|_| StreamsData::default()).collect()
+    }
+
+    #[test]
+    fn split_and_reassemble() {
+        let public = large_batch();
+        let masked = large_batch();
+        let payload = PayloadBuilder::new()
+            .public(&public)
+            .unwrap()
+            .masked(&masked)
+            .unwrap()
+            .build();
+
+        let fragments = split(&payload, 512).unwrap();
+        assert!(fragments.len() > 1);
+        assert!(fragments
+            .iter()
+            .all(|f| f.public_data().0.len() + f.masked_data().0.len() <= 512));
+
+        let mut reassembler = Reassembler::new();
+        let (last, first) = fragments.split_last().unwrap();
+        for fragment in first {
+            assert!(reassembler
+                .push(fragment.public_data(), fragment.masked_data())
+                .unwrap()
+                .is_none());
+        }
+        let (p, m) = reassembler
+            .push(last.public_data(), last.masked_data())
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            Payload::unwrap_data::<Vec<StreamsData>>(&p).unwrap(),
+            public
+        );
+        assert_eq!(
+            Payload::unwrap_data::<Vec<StreamsData>>(&m).unwrap(),
+            masked
+        );
+    }
+
+    #[test]
+    fn small_payload_is_not_fragmented() {
+        let payload = PayloadBuilder::new()
+            .public(&StreamsData::default())
+            .unwrap()
+            .build();
+
+        let fragments = split(&payload, 1024).unwrap();
+        assert_eq!(fragments.len(), 1);
+        assert!(!is_fragment(&fragments[0].public_data().0));
+
+        let mut reassembler = Reassembler::new();
+        let (p, _) = reassembler
+            .push(fragments[0].public_data(), fragments[0].masked_data())
+            .unwrap()
+            .unwrap();
+        assert_eq!(&p, payload.public_data());
+    }
+
+    #[test]
+    fn corrupted_fragment_is_rejected() {
+        let payload = PayloadBuilder::new()
+            .public(&large_batch())
+            .unwrap()
+            .build();
+        let mut fragments = split(&payload, 512).unwrap();
+        fragments[1].public.0[HEADER_LEN] ^= 0xFF;
+
+        let mut reassembler = Reassembler::new();
+        let results: Vec<_> = fragments
+            .iter()
+            .map(|f| reassembler.push(f.public_data(), f.masked_data()))
+            .collect();
+        assert!(results.last().unwrap().is_err());
+    }
+
+    #[test]
+    fn oversized_fragment_is_rejected() {
+        let mut public = Vec::new();
+        Header {
+            index: 0,
+            count: 2,
+            total_len: u32::MAX,
+            digest: [0u8; 32],
+        }
+        .encode(&mut public);
+        public.push(0);
+
+        let mut reassembler = Reassembler::new();
+        assert!(reassembler
+            .push(&Bytes(public), &Bytes(Vec::new()))
+            .is_err());
+        assert!(!reassembler.is_pending());
+    }
+
+    #[test]
+    fn last_index_fragment_is_rejected() {
+        let mut public = Vec::new();
+        Header {
+            index: u16::MAX,
+            count: u16::MAX,
+            total_len: 1,
+            digest: [0u8; 32],
+        }
+        .encode(&mut public);
+        public.push(0);
+
+        let mut reassembler = Reassembler::new();
+        match reassembler.push(&Bytes(public), &Bytes(Vec::new())) {
+            Err(PayloadError::InvalidFragment(message)) => {
+                assert_eq!(message, "Unexpected fragment 65536/65535")
+            }
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn out_of_order_fragment_is_rejected() {
+        let payload = PayloadBuilder::new()
+            .public(&large_batch())
+            .unwrap()
+            .build();
+        let fragments = split(&payload, 512).unwrap();
+
+        let mut reassembler = Reassembler::new();
+        assert!(reassembler
+            .push(fragments[1].public_data(), &Bytes(Vec::new()))
+            .is_err());
+        assert!(!reassembler.is_pending());
+    }
+}
@@ -0,0 +1,263 @@
+//!
+//! CloudEvents module
+//!
+//! Payloads wrapped in a [CloudEvents 1.0](https://github.com/cloudevents/spec) structured mode
+//! JSON envelope instead of the binary envelope. JSON data is embedded as is, any other data
+//! is written in `data_base64`.
+//!
+//! The events written by [`PayloadBuilder`](super::PayloadBuilder) are identified by the
+//! blake2b digest of their envelope and data, so building the same data at the same time gives
+//! the same event.
+//!
+use super::{
+    digest::content_digest, encryption::GroupKeyRing, envelope::Envelope, signature::TrustedKeys,
+    PayloadError,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+///
+/// Media type of the CloudEvents structured mode
+///
+pub const CONTENT_TYPE: &str = "application/cloudevents+json";
+
+///
+/// Supported CloudEvents specification version
+///
+pub const SPEC_VERSION: &str = "1.0";
+
+///
+/// CloudEvent
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CloudEvent {
+    /// CloudEvents specification version
+    pub specversion: String,
+    /// Event identifier, unique for each source
+    pub id: String,
+    /// Event source, usually the channel address
+    pub source: String,
+    /// Event type, the type identifier of the data
+    #[serde(rename = "type")]
+    pub event_type: String,
+    /// Creation time of the event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time: Option<DateTime<Utc>>,
+    /// Media type of the data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub datacontenttype: Option<String>,
+    /// Schema version of the data, extension attribute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schemaversion: Option<u16>,
+    /// JSON data
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<serde_json::Value>,
+    /// Binary data, base64 encoded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data_base64: Option<String>,
+}
+
+impl CloudEvent {
+    ///
+    /// Create the event of the data described by the envelope
+    ///
+    pub fn new(
+        envelope: &Envelope,
+        source: &str,
+        id: &str,
+        data: &[u8],
+    ) -> Result<Self, PayloadError> {
+        let (data, data_base64) = if is_json(&envelope.content_type) {
+            let value = serde_json::from_slice(data)
+                .map_err(|e| PayloadError::InvalidEncoding(format!("CloudEvent data: {}", e)))?;
+            (Some(value), None)
+        } else {
+            (None, Some(base64::encode(data)))
+        };
+        Ok(CloudEvent {
+            specversion: SPEC_VERSION.to_string(),
+            id: id.to_string(),
+            source: source.to_string(),
+            event_type: envelope.type_id.clone(),
+            time: envelope.timestamp,
+            datacontenttype: Some(envelope.content_type.clone()),
+            schemaversion: Some(envelope.version),
+            data,
+            data_base64,
+        })
+    }
+
+    ///
+    /// Create the event of any payload data, with the given source and identifier
+    ///
+    /// The data encrypted with a group key or signed is rejected, the event would carry it in
+    /// clear or without its signature
+    ///
+    pub fn from_payload(data: &[u8], source: &str, id: &str) -> Result<Self, PayloadError> {
+        let (envelope, raw) = super::open(data, &GroupKeyRing::new(), &TrustedKeys::new())?;
+        Self::new(&envelope, source, id, &raw)
+    }
+
+    ///
+    /// Parse the JSON event
+    ///
+    pub fn parse(data: &[u8]) -> Result<Self, PayloadError> {
+        let event: CloudEvent = serde_json::from_slice(data)
+            .map_err(|e| PayloadError::InvalidEncoding(format!("CloudEvent: {}", e)))?;
+        if event.specversion != SPEC_VERSION {
+            return Err(PayloadError::InvalidEncoding(format!(
+                "Unsupported CloudEvents version {}",
+                event.specversion
+            )));
+        }
+        Ok(event)
+    }
+
+    ///
+    /// Write the JSON event
+    ///
+    pub fn to_vec(&self) -> anyhow::Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+
+    ///
+    /// Envelope and data of the event, the envelope has no timestamp when the event has no
+    /// `time`
+    ///
+    pub fn open(&self) -> Result<(Envelope, Vec<u8>), PayloadError> {
+        let data = match (&self.data, &self.data_base64) {
+            (Some(value), _) => serde_json::to_vec(value)
+                .map_err(|e| PayloadError::InvalidEncoding(format!("CloudEvent data: {}", e)))?,
+            (None, Some(encoded)) => base64::decode(encoded)
+                .map_err(|e| PayloadError::InvalidEncoding(format!("CloudEvent data: {}", e)))?,
+            (None, None) => Vec::new(),
+        };
+        let envelope = Envelope {
+            type_id: self.event_type.clone(),
+            version: self.schemaversion.unwrap_or(1),
+            content_type: self
+                .datacontenttype
+                .clone()
+                .unwrap_or_else(|| "application/json".to_string()),
+            timestamp: self.time,
+        };
+        Ok((envelope, data))
+    }
+}
+
+///
+/// Check if the data is a JSON event rather than a binary envelope
+///
+pub fn is_cloud_event(data: &[u8]) -> bool {
+    data.first() == Some(&b'{')
+}
+
+///
+/// Wrap the data described by the envelope in a JSON event, identified by the first 12 bytes
+/// of the digest of the envelope and the data
+///
+pub fn wrap(envelope: &Envelope, source: &str, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let id = hex::encode(&content_digest(&envelope.wrap(data)?)[..12]);
+    CloudEvent::new(envelope, source, &id, data)?.to_vec()
+}
+
+///
+/// Check if the media type is JSON
+///
+pub(crate) fn is_json(content_type: &str) -> bool {
+    content_type == "application/json" || content_type.ends_with("+json")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_cloud_event, CloudEvent};
+    use crate::{
+        clock::FakeClock,
+        payload::{envelope::Envelope, json, PacketPayload},
+        sample::StreamsData,
+    };
+    use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
+
+    const SOURCE: &str = "2bd36986053a06f546e0e3f525ad849e5cc1610fe65ade1cc4e4312bac524281";
+
+    #[test]
+    fn structured_json_event() {
+        let data = StreamsData::default();
+        let payload = json::PayloadBuilder::cloud_events(SOURCE)
+            .public(&data)
+            .unwrap()
+            .build();
+        assert!(is_cloud_event(&payload.public_data().0));
+
+        let event = CloudEvent::parse(&payload.public_data().0).unwrap();
+        assert_eq!(event.source, SOURCE);
+        assert_eq!(event.event_type, "streams-data");
+        assert_eq!(event.id.len(), 24);
+        assert_eq!(event.datacontenttype.as_deref(), Some("application/json"));
+        assert!(event.data.is_some());
+
+        let public: StreamsData = json::Payload::unwrap_data(payload.public_data()).unwrap();
+        assert_eq!(public, data);
+
+        let clock = Arc::new(FakeClock::fixed(
+            Utc.timestamp_opt(1_617_300_000, 0).unwrap(),
+        ));
+        let build = || {
+            json::PayloadBuilder::cloud_events(SOURCE)
+                .clock(clock.clone())
+                .public(&data)
+                .unwrap()
+                .build()
+        };
+        let id = |payload: json::Payload| CloudEvent::parse(&payload.public_data().0).unwrap().id;
+        assert_eq!(id(build()), id(build()));
+    }
+
+    #[test]
+    fn event_without_time() {
+        let mut event = CloudEvent::new(
+            &Envelope::new::<StreamsData, json::JsonSerializer>(),
+            SOURCE,
+            "msg",
+            b"{}",
+        )
+        .unwrap();
+        event.time = None;
+
+        let raw = event.to_vec().unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&raw).unwrap();
+        assert!(value.get("time").is_none());
+
+        let parsed = CloudEvent::parse(&raw).unwrap();
+        assert!(parsed.time.is_none());
+        let (envelope, _) = parsed.open().unwrap();
+        assert_eq!(envelope.timestamp, None);
+    }
+
+    #[test]
+    fn binary_data_event() {
+        let mut envelope = Envelope::new::<StreamsData, json::JsonSerializer>();
+        envelope.content_type = "application/octet-stream".to_string();
+        let event = CloudEvent::new(&envelope, SOURCE, "msg", &[0xE1, 0x00, 0xFF]).unwrap();
+        assert!(event.data.is_none());
+
+        let parsed = CloudEvent::parse(&event.to_vec().unwrap()).unwrap();
+        let (opened, raw) = parsed.open().unwrap();
+        assert_eq!(opened.content_type, "application/octet-stream");
+        assert_eq!(raw, vec![0xE1, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn event_from_binary_envelope() {
+        let data = StreamsData::default();
+        let payload = json::PayloadBuilder::new().masked(&data).unwrap().build();
+
+        let event = CloudEvent::from_payload(&payload.masked_data().0, SOURCE, "msg").unwrap();
+        assert_eq!(event.id, "msg");
+        assert_eq!(event.schemaversion, Some(1));
+        let (envelope, raw) = event.open().unwrap();
+        assert_eq!(envelope.type_id, "streams-data");
+        assert_eq!(serde_json::from_slice::<StreamsData>(&raw).unwrap(), data);
+    }
+}
@@ -0,0 +1,351 @@
+//!
+//! Payload Compression module
+//!
+//! Wraps any [`PayloadSerializer`] and compresses the serialized bytes. The first byte of
+//! the packet tells the reader how the rest was written:
+//!
+//! * `0x00`: uncompressed, used when compression would not save any byte
+//! * `0x01`: zstd (feature `zstd`)
+//! * `0x02`: deflate (feature `deflate`)
+//!
+//! The content type of the envelope is the one of the serializer followed by the backend, e.g.
+//! `application/json+zstd`, so the readers of the plain serializer reject the packet and the
+//! schema validation and CloudEvents don't read it as JSON.
+//!
+//! Decompressed data over [`MAX_DECODED_BYTES`](super::MAX_DECODED_BYTES) is rejected, so a small packet can't expand
+//! to gigabytes on the subscriber.
+//!
+//! ```ignore
+//! use poc::payload::{compress::{Compressed, Zstd}, json::JsonSerializer, PayloadBuilder};
+//!
+//! let payload = PayloadBuilder::<Compressed<JsonSerializer, Zstd>>::new()
+//!     .public(&data)?
+//!     .build();
+//! ```
+//!
+use super::PayloadSerializer;
+use serde::{de::DeserializeOwned, Serialize};
+use std::{borrow::Cow, marker::PhantomData};
+
+///
+/// Header byte of the packets stored without compression
+///
+pub const UNCOMPRESSED: u8 = 0x00;
+
+///
+/// Content type suffixes of the compression backends, enabled or not
+///
+pub const CODECS: [&str; 2] = ["zstd", "deflate"];
+
+///
+/// Compression Backend
+///
+pub trait Compressor {
+    ///
+    /// Header byte which identifies the backend
+    ///
+    const ID: u8;
+
+    ///
+    /// Name added to the content type of the serializer, one of [`CODECS`]
+    ///
+    const NAME: &'static str;
+
+    ///
+    /// Compress data
+    ///
+    fn compress(data: &[u8]) -> anyhow::Result<Vec<u8>>;
+
+    ///
+    /// Decompress data
+    ///
+    fn decompress(data: &[u8]) -> anyhow::Result<Vec<u8>>;
+}
+
+///
+/// Serializer `S` with compression backend `C`
+///
+pub struct Compressed<S, C> {
+    _marker: PhantomData<(S, C)>,
+}
+
+impl<S, C> PayloadSerializer for Compressed<S, C>
+where
+    S: PayloadSerializer,
+    C: Compressor,
+{
+    fn content_type() -> Cow<'static, str> {
+        Cow::Owned(format!("{}+{}", S::content_type(), C::NAME))
+    }
+
+    fn serialize_data<T: Serialize>(data: &T) -> anyhow::Result<Vec<u8>> {
+        let raw = S::serialize_data(data)?;
+        let compressed = C::compress(&raw)?;
+
+        let (header, body) = if compressed.len() < raw.len() {
+            (C::ID, compressed)
+        } else {
+            (UNCOMPRESSED, raw)
+        };
+        let mut packet = Vec::with_capacity(body.len() + 1);
+        packet.push(header);
+        packet.extend_from_slice(&body);
+        Ok(packet)
+    }
+
+    fn deserialize_data<T: DeserializeOwned>(data: &[u8]) -> anyhow::Result<T> {
+        S::deserialize_data(&unpack(data)?)
+    }
+}
+
+///
+/// Content type of the serializer of a compressed content type, `None` when the content type is
+/// not compressed
+///
+pub fn serializer_content_type(content_type: &str) -> Option<&str> {
+    let (serializer, codec) = content_type.rsplit_once('+')?;
+    CODECS.contains(&codec).then_some(serializer)
+}
+
+///
+/// Serialized data of a packet, decompressed if needed
+///
+pub fn unpack(packet: &[u8]) -> anyhow::Result<Cow<'_, [u8]>> {
+    let (header, body) = packet
+        .split_first()
+        .ok_or_else(|| anyhow::anyhow!("Missing compression header"))?;
+
+    if *header == UNCOMPRESSED {
+        Ok(Cow::Borrowed(body))
+    } else {
+        decompress(*header, body).map(Cow::Owned)
+    }
+}
+
+///
+/// Decompress a packet body with the backend identified by the header byte
+///
+/// Readers are not tied to the backend used to write the packet, any enabled backend is accepted
+///
+pub fn decompress(header: u8, body: &[u8]) -> anyhow::Result<Vec<u8>> {
+    match header {
+        #[cfg(feature = "zstd")]
+        Zstd::ID => Zstd::decompress(body),
+        #[cfg(feature = "deflate")]
+        Deflate::ID => Deflate::decompress(body),
+        _ => Err(anyhow::anyhow!(
+            "Unsupported compression header {:#04x} ({} bytes)",
+            header,
+            body.len()
+        )),
+    }
+}
+
+///
+/// Read the decompressed data, failing when it exceeds `MAX_DECODED_BYTES`
+///
+#[cfg(any(feature = "zstd", feature = "deflate"))]
+fn read_limited<R: std::io::Read>(reader: R) -> anyhow::Result<Vec<u8>> {
+    use super::MAX_DECODED_BYTES;
+    use std::io::Read;
+
+    let mut decoded = Vec::new();
+    reader
+        .take(MAX_DECODED_BYTES as u64 + 1)
+        .read_to_end(&mut decoded)
+        .map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+    if decoded.len() > MAX_DECODED_BYTES {
+        return Err(anyhow::anyhow!(
+            "Decompressed data exceeds {} bytes",
+            MAX_DECODED_BYTES
+        ));
+    }
+    Ok(decoded)
+}
+
+///
+/// Zstandard Compression
+///
+#[cfg(feature = "zstd")]
+pub struct Zstd;
+
+#[cfg(feature = "zstd")]
+impl Zstd {
+    ///
+    /// Compression level
+    ///
+    pub const LEVEL: i32 = 19;
+}
+
+#[cfg(feature = "zstd")]
+impl Compressor for Zstd {
+    const ID: u8 = 0x01;
+    const NAME: &'static str = "zstd";
+
+    fn compress(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        zstd::encode_all(data, Self::LEVEL).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+
+    fn decompress(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let decoder =
+            zstd::stream::read::Decoder::new(data).map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+        read_limited(decoder)
+    }
+}
+
+///
+/// Deflate Compression
+///
+#[cfg(feature = "deflate")]
+pub struct Deflate;
+
+#[cfg(feature = "deflate")]
+impl Compressor for Deflate {
+    const ID: u8 = 0x02;
+    const NAME: &'static str = "deflate";
+
+    fn compress(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        use flate2::{write::DeflateEncoder, Compression};
+        use std::io::Write;
+
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+        encoder
+            .write_all(data)
+            .and_then(|_| encoder.finish())
+            .map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+
+    fn decompress(data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        read_limited(flate2::read::DeflateDecoder::new(data))
+    }
+}
+
+#[cfg(all(test, any(feature = "zstd", feature = "deflate")))]
+mod tests {
+    use super::{serializer_content_type, Compressed, Compressor, UNCOMPRESSED};
+    use crate::{
+        payload::{
+            cloudevents::CloudEvent, envelope::Envelope, json::JsonSerializer,
+            registry::DecoderRegistry, PacketPayload, Payload, PayloadBuilder, PayloadError,
+            PayloadSerializer, MAX_DECODED_BYTES,
+        },
+        sample::{self, StreamsData},
+    };
+
+    fn batch() -> Vec<StreamsData> {
+        (0..20)
+            .map(|i| StreamsData::new("Sensor located in the north wing", i as f32, 1.0e3))
+            .collect()
+    }
+
+    fn roundtrip<C: Compressor>() {
+        let batch = batch();
+        let payload = PayloadBuilder::<Compressed<JsonSerializer, C>>::new()
+            .public(&batch)
+            .unwrap()
+            .build();
+
+        let raw = JsonSerializer::serialize_data(&batch).unwrap();
+        let (envelope, packet) = Envelope::open(&payload.public_data().0).unwrap();
+        assert_eq!(packet[0], C::ID);
+        assert!(packet.len() < raw.len());
+        assert_eq!(
+            envelope.content_type,
+            format!("application/json+{}", C::NAME)
+        );
+        assert_eq!(
+            serializer_content_type(&envelope.content_type),
+            Some("application/json")
+        );
+
+        let decoded: Vec<StreamsData> =
+            Payload::<Compressed<JsonSerializer, C>>::unwrap_data(payload.public_data()).unwrap();
+        assert_eq!(decoded, batch);
+        assert!(matches!(
+            Payload::<JsonSerializer>::unwrap_data::<Vec<StreamsData>>(payload.public_data()),
+            Err(PayloadError::UnknownSchema { .. })
+        ));
+    }
+
+    fn registry_and_schemas<C: Compressor + 'static>() {
+        // Long enough to be compressed
+        let hot = StreamsData::new("Boiler room, north wing. ".repeat(8), 1.0e6, 101_325.0);
+        let data = StreamsData::new("Boiler room", 21.5, 101_325.0);
+        let payload = PayloadBuilder::<Compressed<JsonSerializer, C>>::new()
+            .public(&data)
+            .unwrap()
+            .masked(&hot)
+            .unwrap()
+            .build();
+
+        let mut registry = DecoderRegistry::new();
+        registry.register::<StreamsData, JsonSerializer, _>(|data| data);
+        assert!(matches!(
+            registry.decode(payload.public_data()),
+            Err(PayloadError::UnknownSchema { .. })
+        ));
+
+        registry
+            .register::<StreamsData, Compressed<JsonSerializer, C>, _>(|data| data)
+            .validate_with(sample::schemas());
+        let public = registry.decode(payload.public_data()).unwrap();
+        assert!(public.validation.unwrap().is_valid());
+        assert_eq!(public.message, data);
+        assert!(matches!(
+            registry.decode(payload.masked_data()),
+            Err(PayloadError::SchemaViolation(_))
+        ));
+    }
+
+    fn cloud_events<C: Compressor>() {
+        let batch = batch();
+        let payload = PayloadBuilder::<Compressed<JsonSerializer, C>>::cloud_events("source")
+            .public(&batch)
+            .unwrap()
+            .build();
+
+        let event = CloudEvent::parse(&payload.public_data().0).unwrap();
+        assert!(event.data.is_none());
+        assert!(event.data_base64.is_some());
+        let decoded: Vec<StreamsData> =
+            Payload::<Compressed<JsonSerializer, C>>::unwrap_data(payload.public_data()).unwrap();
+        assert_eq!(decoded, batch);
+    }
+
+    fn uncompressed_fallback<C: Compressor>() {
+        let packet = Compressed::<JsonSerializer, C>::serialize_data(&1u8).unwrap();
+        assert_eq!(packet, vec![UNCOMPRESSED, b'1']);
+
+        let value: u8 = Compressed::<JsonSerializer, C>::deserialize_data(&packet).unwrap();
+        assert_eq!(value, 1);
+    }
+
+    fn reject_bomb<C: Compressor>() {
+        let bomb = C::compress(&vec![0u8; MAX_DECODED_BYTES + 1]).unwrap();
+        assert!(bomb.len() < 16 * 1024);
+        assert!(C::decompress(&bomb).is_err());
+
+        let fits = C::compress(&vec![0u8; MAX_DECODED_BYTES]).unwrap();
+        assert_eq!(C::decompress(&fits).unwrap().len(), MAX_DECODED_BYTES);
+    }
+
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_roundtrip() {
+        roundtrip::<super::Zstd>();
+        uncompressed_fallback::<super::Zstd>();
+        reject_bomb::<super::Zstd>();
+        registry_and_schemas::<super::Zstd>();
+        cloud_events::<super::Zstd>();
+    }
+
+    #[cfg(feature = "deflate")]
+    #[test]
+    fn deflate_roundtrip() {
+        roundtrip::<super::Deflate>();
+        uncompressed_fallback::<super::Deflate>();
+        reject_bomb::<super::Deflate>();
+        registry_and_schemas::<super::Deflate>();
+        cloud_events::<super::Deflate>();
+    }
+}
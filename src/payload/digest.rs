@@ -0,0 +1,164 @@
+//!
+//! Payload Digest module
+//!
+//! Blake2b-256 digest of the serialized data, without the envelope, so the same data always
+//! has the same digest and works as a content address. A second digest covers the envelope
+//! header and the data, so corrupted payloads are detected wherever the corruption is.
+//!
+//! | magic | data digest | envelope digest | envelope |
+//! |-------|-------------|-----------------|----------|
+//! | 2     | 32          | 32              | ...      |
+//!
+use super::PayloadError;
+use crypto::hashes::{blake2b, Digest};
+
+///
+/// Magic bytes of the data with digest
+///
+pub const MAGIC: [u8; 2] = [0xD1, 0x02];
+
+///
+/// Length of the digest header
+///
+pub const HEADER_LEN: usize = MAGIC.len() + 64;
+
+///
+/// Check if the data carries a digest
+///
+pub fn has_digest(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+///
+/// Blake2b-256 digest of the serialized data
+///
+pub fn content_digest(data: &[u8]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&blake2b::Blake2b256::digest(data));
+    out
+}
+
+///
+/// Write the digests of the serialized data and of the envelope, followed by the envelope
+///
+pub fn wrap(data: &[u8], envelope: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(HEADER_LEN + envelope.len());
+    out.extend_from_slice(&MAGIC);
+    out.extend_from_slice(&content_digest(data));
+    out.extend_from_slice(&content_digest(envelope));
+    out.extend_from_slice(envelope);
+    out
+}
+
+///
+/// Read the digest header and check the digest of the envelope, returns the digest of the
+/// serialized data and the envelope
+///
+pub fn split(data: &[u8]) -> Result<([u8; 32], &[u8]), PayloadError> {
+    if !has_digest(data) || data.len() < HEADER_LEN {
+        return Err(PayloadError::InvalidEncoding(
+            "Truncated payload digest".to_string(),
+        ));
+    }
+    let (header, envelope) = data.split_at(HEADER_LEN);
+    let (mut digest, mut envelope_digest) = ([0u8; 32], [0u8; 32]);
+    digest.copy_from_slice(&header[MAGIC.len()..MAGIC.len() + 32]);
+    envelope_digest.copy_from_slice(&header[MAGIC.len() + 32..]);
+    verify(&envelope_digest, envelope)?;
+    Ok((digest, envelope))
+}
+
+///
+/// Check the digest of the data
+///
+pub fn verify(digest: &[u8; 32], data: &[u8]) -> Result<(), PayloadError> {
+    if content_digest(data) != *digest {
+        return Err(PayloadError::DigestMismatch);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HEADER_LEN;
+    use crate::{
+        payload::{
+            envelope::{self, Envelope},
+            json,
+            signature::TrustedKeys,
+            PacketPayload, PayloadError,
+        },
+        sample::StreamsData,
+    };
+
+    #[test]
+    fn same_data_same_digest() {
+        let data = StreamsData::default();
+        let build = || {
+            json::PayloadBuilder::new()
+                .public(&data)
+                .unwrap()
+                .digest()
+                .unwrap()
+                .build()
+        };
+        let (first, second) = (build(), build());
+
+        let trusted = TrustedKeys::new();
+        let digest = json::Payload::digest(first.public_data(), &trusted).unwrap();
+        assert!(digest.is_some());
+        assert_eq!(
+            digest,
+            json::Payload::digest(second.public_data(), &trusted).unwrap()
+        );
+        assert_eq!(
+            json::Payload::unwrap_data::<StreamsData>(first.public_data()).unwrap(),
+            data
+        );
+    }
+
+    #[test]
+    fn reject_corrupted_data() {
+        let payload = json::PayloadBuilder::new()
+            .masked(&StreamsData::new("Boiler room", 21.5, 101_325.0))
+            .unwrap()
+            .digest()
+            .unwrap()
+            .build();
+
+        let mut corrupted = payload.masked_data().clone();
+        let last = corrupted.0.len() - 2;
+        corrupted.0[last] = b'9';
+        assert!(matches!(
+            json::Payload::unwrap_data::<StreamsData>(&corrupted),
+            Err(PayloadError::DigestMismatch)
+        ));
+    }
+
+    #[test]
+    fn reject_corrupted_envelope() {
+        let payload = json::PayloadBuilder::new()
+            .public(&StreamsData::default())
+            .unwrap()
+            .digest()
+            .unwrap()
+            .build();
+        let (opened, _) = Envelope::open(&payload.public_data().0[HEADER_LEN..]).unwrap();
+
+        // Last byte of the timestamp, the envelope still reads
+        let timestamp = HEADER_LEN
+            + envelope::MAGIC.len()
+            + 1
+            + opened.type_id.len()
+            + 2
+            + 1
+            + opened.content_type.len();
+        let mut corrupted = payload.public_data().clone();
+        corrupted.0[timestamp + 7] ^= 0x01;
+        assert!(Envelope::open(&corrupted.0[HEADER_LEN..]).is_ok());
+        assert!(matches!(
+            json::Payload::unwrap_data::<StreamsData>(&corrupted),
+            Err(PayloadError::DigestMismatch)
+        ));
+    }
+}
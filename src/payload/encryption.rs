@@ -0,0 +1,302 @@
+//!
+//! Group Key Encryption module
+//!
+//! Masked data encrypted with XChaCha20-Poly1305 using a named group key, on top of the
+//! Streams keyload. Only the subscribers holding the group key can read the data, so different
+//! consumer groups of one channel can see different masked data.
+//!
+//! | magic | key name      | nonce | ciphertext + tag |
+//! |-------|---------------|-------|------------------|
+//! | 2     | u8 len + utf8 | 24    | ...              |
+//!
+//! The group keys are passed to [`Payload::unwrap_data_with`](super::Payload::unwrap_data_with)
+//! or held by the [`DecoderRegistry`](super::registry::DecoderRegistry) with
+//! [`decrypt_with`](super::registry::DecoderRegistry::decrypt_with)
+//!
+use super::PayloadError;
+use crate::keystore;
+use chacha20poly1305::{
+    aead::{Aead, NewAead, Payload as AeadPayload},
+    Key, XChaCha20Poly1305, XNonce,
+};
+use rand::{rngs::OsRng, Rng};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{collections::HashMap, fmt, fs, path::Path};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
+
+///
+/// Magic bytes of the encrypted data
+///
+pub const MAGIC: [u8; 2] = [0xC5, 0x01];
+
+const NONCE_LEN: usize = 24;
+
+const TAG_LEN: usize = 16;
+
+///
+/// 256 bits Group Key, zeroized on drop and compared in constant time
+///
+#[derive(Clone)]
+pub struct GroupKey([u8; 32]);
+
+impl GroupKey {
+    ///
+    /// Generate a random key from the OS random number generator
+    ///
+    pub fn generate() -> Self {
+        GroupKey(OsRng.gen())
+    }
+
+    ///
+    /// Create the key from its bytes
+    ///
+    pub fn from_bytes(bytes: [u8; 32]) -> Self {
+        GroupKey(bytes)
+    }
+
+    ///
+    /// Create the key from its hex representation
+    ///
+    pub fn from_hex(value: &str) -> anyhow::Result<Self> {
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        hex::decode_to_slice(value, &mut *bytes).map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+        Ok(GroupKey(*bytes))
+    }
+
+    ///
+    /// Hex representation of the key
+    ///
+    pub fn to_hex(&self) -> Zeroizing<String> {
+        Zeroizing::new(hex::encode(&self.0[..]))
+    }
+}
+
+impl PartialEq for GroupKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ct_eq(&other.0).into()
+    }
+}
+
+impl Eq for GroupKey {}
+
+impl Drop for GroupKey {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl fmt::Debug for GroupKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("GroupKey(..)")
+    }
+}
+
+impl Serialize for GroupKey {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_hex())
+    }
+}
+
+impl<'de> Deserialize<'de> for GroupKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = Zeroizing::new(String::deserialize(deserializer)?);
+        GroupKey::from_hex(&value).map_err(de::Error::custom)
+    }
+}
+
+///
+/// Named group keys, stored as a JSON file readable by the owner only
+///
+/// The file is not encrypted, unlike the seeds of the [keystore](crate::keystore) the keys are
+/// only protected by the file permissions
+///
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct GroupKeyRing {
+    keys: HashMap<String, GroupKey>,
+}
+
+impl GroupKeyRing {
+    ///
+    /// Create Instance
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Load the key ring file
+    ///
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let raw = Zeroizing::new(fs::read(path)?);
+        serde_json::from_slice(&raw).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+
+    ///
+    /// Write the key ring file, see [`write_private`](crate::keystore::write_private)
+    ///
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let raw = Zeroizing::new(
+            serde_json::to_vec_pretty(self).map_err(|e| anyhow::anyhow!("{:#?}", e))?,
+        );
+        keystore::write_private(path, &raw)
+    }
+
+    ///
+    /// Add a key, replacing the key with the same name
+    ///
+    pub fn insert<N: Into<String>>(&mut self, name: N, key: GroupKey) -> &mut Self {
+        self.keys.insert(name.into(), key);
+        self
+    }
+
+    ///
+    /// Find a key by name
+    ///
+    pub fn get(&self, name: &str) -> Option<&GroupKey> {
+        self.keys.get(name)
+    }
+
+    ///
+    /// Remove a key by name
+    ///
+    pub fn remove(&mut self, name: &str) -> Option<GroupKey> {
+        self.keys.remove(name)
+    }
+}
+
+///
+/// Check if the data is encrypted with a group key
+///
+pub fn is_encrypted(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+///
+/// Bytes added to the data by the encryption with the named key
+///
+pub fn overhead(name: &str) -> usize {
+    MAGIC.len() + 1 + name.len() + NONCE_LEN + TAG_LEN
+}
+
+///
+/// Encrypt the data with the named group key
+///
+pub fn encrypt(name: &str, key: &GroupKey, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if name.len() > u8::MAX as usize {
+        return Err(anyhow::anyhow!(
+            "Group key names are limited to {} bytes",
+            u8::MAX
+        ));
+    }
+    let mut out = Vec::with_capacity(overhead(name) + data.len());
+    out.extend_from_slice(&MAGIC);
+    out.push(name.len() as u8);
+    out.extend_from_slice(name.as_bytes());
+
+    let nonce: [u8; NONCE_LEN] = OsRng.gen();
+    let sealed = XChaCha20Poly1305::new(Key::from_slice(&key.0))
+        .encrypt(
+            XNonce::from_slice(&nonce),
+            AeadPayload {
+                msg: data,
+                aad: &out,
+            },
+        )
+        .map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+    out.extend_from_slice(&nonce);
+    out.extend_from_slice(&sealed);
+    Ok(out)
+}
+
+///
+/// Decrypt the data with the group key of the key ring it names
+///
+pub fn decrypt(keys: &GroupKeyRing, data: &[u8]) -> Result<Vec<u8>, PayloadError> {
+    let truncated = || PayloadError::InvalidEncoding("Truncated encrypted data".to_string());
+    let name_len = *data.get(MAGIC.len()).ok_or_else(truncated)? as usize;
+    let header_len = MAGIC.len() + 1 + name_len;
+    if !is_encrypted(data) || data.len() < header_len + NONCE_LEN + TAG_LEN {
+        return Err(truncated());
+    }
+    let (header, rest) = data.split_at(header_len);
+    let (nonce, sealed) = rest.split_at(NONCE_LEN);
+    let name = String::from_utf8_lossy(&header[MAGIC.len() + 1..]).to_string();
+
+    let key = keys
+        .get(&name)
+        .ok_or_else(|| PayloadError::UnknownKey(name.clone()))?;
+    XChaCha20Poly1305::new(Key::from_slice(&key.0))
+        .decrypt(
+            XNonce::from_slice(nonce),
+            AeadPayload {
+                msg: sealed,
+                aad: header,
+            },
+        )
+        .map_err(|_| PayloadError::Decrypt(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_encrypted, GroupKey, GroupKeyRing};
+    use crate::{
+        payload::{json, PacketPayload, PayloadError},
+        sample::StreamsData,
+    };
+
+    #[test]
+    fn encrypt_masked_data() {
+        let mut keys = GroupKeyRing::new();
+        keys.insert("test-operators", GroupKey::generate());
+
+        let data = StreamsData::default();
+        let payload = json::PayloadBuilder::new()
+            .public(&data)
+            .unwrap()
+            .masked(&data)
+            .unwrap()
+            .encrypt_masked(&keys, "test-operators")
+            .unwrap()
+            .build();
+        assert!(!is_encrypted(&payload.public_data().0));
+        assert!(is_encrypted(&payload.masked_data().0));
+
+        match json::Payload::unwrap_data::<StreamsData>(payload.masked_data()) {
+            Err(PayloadError::UnknownKey(name)) => assert_eq!(name, "test-operators"),
+            other => panic!("Unexpected result {:?}", other),
+        }
+
+        let masked: StreamsData =
+            json::Payload::unwrap_data_with(payload.masked_data(), &keys, &Default::default())
+                .unwrap();
+        assert_eq!(masked, data);
+
+        // Another key with the same name can not decrypt the data
+        let mut other = GroupKeyRing::new();
+        other.insert("test-operators", GroupKey::generate());
+        assert!(matches!(
+            json::Payload::unwrap_data_with::<StreamsData>(
+                payload.masked_data(),
+                &other,
+                &Default::default()
+            ),
+            Err(PayloadError::Decrypt(_))
+        ));
+    }
+
+    #[test]
+    fn key_ring_file() {
+        let path = std::env::temp_dir().join(format!("poc-keys-{}.json", std::process::id()));
+        let key = GroupKey::generate();
+        GroupKeyRing::new()
+            .insert("maintenance", key.clone())
+            .save(&path)
+            .unwrap();
+
+        let loaded = GroupKeyRing::load(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.get("maintenance"), Some(&key));
+        assert_eq!(GroupKey::from_hex(&key.to_hex()).unwrap(), key);
+    }
+}
@@ -0,0 +1,204 @@
+//!
+//! Payload Envelope module
+//!
+//! Every payload built by [`PayloadBuilder`](super::PayloadBuilder) is wrapped in an envelope
+//! which describes the serialized data:
+//!
+//! | magic | type id       | schema version | content type  | timestamp (ms) | data |
+//! |-------|---------------|----------------|---------------|----------------|------|
+//! | 2     | u8 len + utf8 | u16            | u8 len + utf8 | i64            | ...  |
+//!
+//! A timestamp of `i64::MIN` means the payload has no creation time.
+//!
+use super::{PayloadEncode, PayloadError, PayloadSchema};
+use chrono::{DateTime, TimeZone, Utc};
+
+///
+/// Timestamp written for the envelopes without creation time
+///
+const NO_TIMESTAMP: i64 = i64::MIN;
+
+///
+/// Magic bytes of the envelope header
+///
+pub const MAGIC: [u8; 2] = [0xE1, 0x01];
+
+///
+/// Payload Envelope
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Envelope {
+    /// Type identifier of the data
+    pub type_id: String,
+    /// Schema version of the data
+    pub version: u16,
+    /// Media type of the serialized data
+    pub content_type: String,
+    /// Creation time of the payload, `None` when the writer did not set it
+    pub timestamp: Option<DateTime<Utc>>,
+}
+
+impl Envelope {
+    ///
+    /// Create the envelope of the data type `T` serialized with `S`
+    ///
+    pub fn new<T, S>() -> Self
+    where
+        T: PayloadSchema,
+        S: PayloadEncode<T>,
+    {
+        Self::new_at::<T, S>(Utc::now())
+    }
+
+    ///
+    /// Create the envelope of the data type `T` serialized with `S`, created at the timestamp
+    ///
+    pub fn new_at<T, S>(timestamp: DateTime<Utc>) -> Self
+    where
+        T: PayloadSchema,
+        S: PayloadEncode<T>,
+    {
+        Envelope {
+            type_id: T::schema_type().into_owned(),
+            version: T::schema_version(),
+            content_type: S::content_type().into_owned(),
+            timestamp: Some(timestamp),
+        }
+    }
+
+    ///
+    /// Check if the envelope describes the data type `T`
+    ///
+    pub fn is<T: PayloadSchema>(&self) -> bool {
+        self.type_id == T::schema_type() && self.version == T::schema_version()
+    }
+
+    ///
+    /// Write the envelope header followed by the data
+    ///
+    pub fn wrap(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if self.type_id.len() > u8::MAX as usize || self.content_type.len() > u8::MAX as usize {
+            return Err(anyhow::anyhow!(
+                "Envelope type id and content type are limited to {} bytes",
+                u8::MAX
+            ));
+        }
+        let mut out = Vec::with_capacity(
+            MAGIC.len() + self.type_id.len() + self.content_type.len() + 12 + data.len(),
+        );
+        out.extend_from_slice(&MAGIC);
+        out.push(self.type_id.len() as u8);
+        out.extend_from_slice(self.type_id.as_bytes());
+        out.extend_from_slice(&self.version.to_be_bytes());
+        out.push(self.content_type.len() as u8);
+        out.extend_from_slice(self.content_type.as_bytes());
+        let millis = self
+            .timestamp
+            .map_or(NO_TIMESTAMP, |timestamp| timestamp.timestamp_millis());
+        out.extend_from_slice(&millis.to_be_bytes());
+        out.extend_from_slice(data);
+        Ok(out)
+    }
+
+    ///
+    /// Read the envelope header, returns the envelope and the wrapped data
+    ///
+    pub fn open(data: &[u8]) -> Result<(Self, &[u8]), PayloadError> {
+        let mut reader = Reader(data);
+
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(PayloadError::InvalidEncoding(
+                "Missing payload envelope".to_string(),
+            ));
+        }
+        let type_id = reader.string()?;
+        let version = u16::from_be_bytes(reader.array()?);
+        let content_type = reader.string()?;
+        let millis = i64::from_be_bytes(reader.array()?);
+        let timestamp = match millis {
+            NO_TIMESTAMP => None,
+            _ => Some(Utc.timestamp_millis_opt(millis).single().ok_or_else(|| {
+                PayloadError::InvalidEncoding(format!("Invalid envelope timestamp {}", millis))
+            })?),
+        };
+
+        Ok((
+            Envelope {
+                type_id,
+                version,
+                content_type,
+                timestamp,
+            },
+            reader.0,
+        ))
+    }
+}
+
+///
+/// Cursor over the envelope header
+///
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PayloadError> {
+        if self.0.len() < len {
+            return Err(PayloadError::InvalidEncoding(
+                "Truncated payload envelope".to_string(),
+            ));
+        }
+        let (head, tail) = self.0.split_at(len);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], PayloadError> {
+        let mut out = [0u8; N];
+        out.copy_from_slice(self.take(N)?);
+        Ok(out)
+    }
+
+    fn string(&mut self) -> Result<String, PayloadError> {
+        let len = self.take(1)?[0] as usize;
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|e| PayloadError::InvalidEncoding(format!("Envelope string: {}", e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Envelope;
+    use crate::{payload::json::JsonSerializer, sample::StreamsData};
+
+    #[test]
+    fn wrap_and_open() {
+        let envelope = Envelope::new::<StreamsData, JsonSerializer>();
+        let packet = envelope.wrap(b"{}").unwrap();
+
+        let (opened, data) = Envelope::open(&packet).unwrap();
+        assert_eq!(opened.type_id, "streams-data");
+        assert_eq!(opened.content_type, "application/json");
+        assert_eq!(
+            opened.timestamp.map(|t| t.timestamp_millis()),
+            envelope.timestamp.map(|t| t.timestamp_millis())
+        );
+        assert!(opened.is::<StreamsData>());
+        assert_eq!(data, b"{}");
+    }
+
+    #[test]
+    fn missing_timestamp() {
+        let mut envelope = Envelope::new::<StreamsData, JsonSerializer>();
+        envelope.timestamp = None;
+        let packet = envelope.wrap(b"{}").unwrap();
+        assert_eq!(Envelope::open(&packet).unwrap().0, envelope);
+    }
+
+    #[test]
+    fn reject_truncated() {
+        let packet = Envelope::new::<StreamsData, JsonSerializer>()
+            .wrap(&[])
+            .unwrap();
+        assert!(Envelope::open(&packet[..packet.len() - 1]).is_err());
+        assert!(Envelope::open(b"{}").is_err());
+    }
+}
@@ -0,0 +1,77 @@
+//!
+//! Payload Error module
+//!
+use super::{validation::ValidationReport, PayloadBudget};
+use thiserror::Error;
+
+///
+/// Error returned when a payload can not be unwrapped
+///
+#[derive(Debug, Error)]
+pub enum PayloadError {
+    /// The payload has no data
+    #[error("Empty payload")]
+    Empty,
+
+    /// The payload framing is malformed
+    #[error("Invalid payload encoding: {0}")]
+    InvalidEncoding(String),
+
+    /// The payload data can not be deserialized
+    #[error("Failed to deserialize {} bytes: {source}", bytes.len())]
+    Deserialize {
+        /// Offending bytes
+        bytes: Vec<u8>,
+        /// Serializer error
+        #[source]
+        source: anyhow::Error,
+    },
+
+    /// The payload envelope describes a type, version or content type which is not expected
+    #[error("Unknown schema {type_id} v{version} ({content_type})")]
+    UnknownSchema {
+        /// Type identifier of the envelope
+        type_id: String,
+        /// Schema version of the envelope
+        version: u16,
+        /// Content type of the envelope
+        content_type: String,
+    },
+
+    /// The fragment sequence of a chunked payload is broken
+    #[error("Invalid fragment: {0}")]
+    InvalidFragment(String),
+
+    /// The data can not be serialized
+    #[error("Failed to serialize: {0}")]
+    Serialize(#[source] anyhow::Error),
+
+    /// The data is encrypted with a group key which is not in the key ring
+    #[error("Missing group key {0}")]
+    UnknownKey(String),
+
+    /// The data can not be decrypted with the group key of the key ring
+    #[error("Failed to decrypt with group key {0}")]
+    Decrypt(String),
+
+    /// The data is signed with a key id which is not trusted, or with another public key than
+    /// the trusted one
+    #[error("Untrusted device key {0}")]
+    UntrustedSigner(String),
+
+    /// The signature of the data does not match the data or the key
+    #[error("Invalid signature of device key {0}")]
+    InvalidSignature(String),
+
+    /// The digest of the data does not match the serialized data
+    #[error("Payload digest mismatch")]
+    DigestMismatch,
+
+    /// The payload does not match the JSON Schema of its type or channel
+    #[error("Schema violation: {0}")]
+    SchemaViolation(ValidationReport),
+
+    /// The payload does not fit in the size limit
+    #[error("Payload too large: {0}")]
+    TooLarge(PayloadBudget),
+}
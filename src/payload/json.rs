@@ -0,0 +1,100 @@
+//!
+//! JSON Payload Serialization module
+//!
+use super::PayloadSerializer;
+use serde::{de::DeserializeOwned, Serialize};
+use std::borrow::Cow;
+
+///
+/// Implementation of JSON Serialize
+///
+pub struct JsonSerializer;
+
+impl PayloadSerializer for JsonSerializer {
+    fn content_type() -> Cow<'static, str> {
+        Cow::Borrowed("application/json")
+    }
+
+    fn serialize_data<T: Serialize>(data: &T) -> anyhow::Result<Vec<u8>> {
+        serde_json::to_vec(data).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+
+    fn deserialize_data<T: DeserializeOwned>(data: &[u8]) -> anyhow::Result<T> {
+        serde_json::from_slice(data).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+}
+
+/// Payload JSON
+///
+pub type Payload = super::Payload<JsonSerializer>;
+
+/// Payload Builder in Json Format
+///
+pub type PayloadBuilder = super::PayloadBuilder<JsonSerializer>;
+
+#[cfg(test)]
+mod tests {
+    use super::{JsonSerializer, Payload, PayloadBuilder};
+    use crate::{
+        payload::{envelope::Envelope, PacketPayload, PayloadError},
+        sample::StreamsData,
+    };
+    use iota_streams::ddml::types::Bytes;
+
+    #[test]
+    fn roundtrip_streams_data() {
+        let data = StreamsData::default();
+        let payload = PayloadBuilder::new().masked(&data).unwrap().build();
+
+        let masked: StreamsData = Payload::unwrap_data(payload.masked_data()).unwrap();
+        assert_eq!(masked, data);
+        assert!(matches!(
+            Payload::unwrap_data::<StreamsData>(payload.public_data()),
+            Err(PayloadError::Empty)
+        ));
+    }
+
+    #[test]
+    fn try_build_checks_budget() {
+        let data = StreamsData::default();
+        let mut builder = PayloadBuilder::new();
+        builder.public(&data).unwrap().masked(&data).unwrap();
+
+        let budget = builder.budget();
+        assert_eq!(budget.used(), budget.public + budget.masked);
+        assert!(builder.limit(budget.used()).try_build().is_ok());
+
+        match builder.limit(budget.public).try_build() {
+            Err(PayloadError::TooLarge(exceeded)) => {
+                assert_eq!(exceeded.headroom(), -(budget.masked as i64));
+                assert!(exceeded.to_string().contains("headroom"));
+            }
+            _ => panic!("Payload should exceed the limit"),
+        }
+    }
+
+    #[test]
+    fn malformed_data_is_reported() {
+        let payload = PayloadBuilder::new()
+            .public(&vec![StreamsData::default()])
+            .unwrap()
+            .build();
+
+        assert!(matches!(
+            Payload::unwrap_data::<StreamsData>(payload.public_data()),
+            Err(PayloadError::UnknownSchema { .. })
+        ));
+        assert!(matches!(
+            Payload::unwrap_data::<StreamsData>(&Bytes(b"{".to_vec())),
+            Err(PayloadError::InvalidEncoding(_))
+        ));
+
+        let packet = Envelope::new::<StreamsData, JsonSerializer>()
+            .wrap(b"{\"ts\":1}")
+            .unwrap();
+        match Payload::unwrap_data::<StreamsData>(&Bytes(packet)) {
+            Err(PayloadError::Deserialize { bytes, .. }) => assert_eq!(bytes, b"{\"ts\":1}"),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
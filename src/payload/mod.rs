@@ -0,0 +1,686 @@
+//!
+//! Payload Module
+//!
+use crate::clock::{Clock, SystemClock};
+use encryption::{GroupKey, GroupKeyRing};
+use envelope::Envelope;
+pub use error::PayloadError;
+use iota_streams::{app::transport::tangle::PAYLOAD_BYTES, ddml::types::Bytes};
+pub use poc_derive::StreamsPayload;
+use serde::{de::DeserializeOwned, Serialize};
+use signature::{DeviceKey, Signer, TrustedKeys};
+use std::{borrow::Cow, fmt, marker::PhantomData};
+
+///
+/// Maximum bytes of the data rebuilt by a reader, decompressed data or reassembled fragments
+/// over it are rejected
+///
+pub const MAX_DECODED_BYTES: usize = 1024 * PAYLOAD_BYTES;
+
+///
+/// Simple Trait to transform the payload to bytes using any serde serializer
+///
+pub trait PayloadSerializer {
+    ///
+    /// Media type of the serialized data
+    ///
+    fn content_type() -> Cow<'static, str>;
+
+    ///
+    /// Transform data to bytes
+    ///
+    fn serialize_data<T: Serialize>(data: &T) -> anyhow::Result<Vec<u8>>;
+
+    ///
+    /// Transform bytes to data
+    ///
+    fn deserialize_data<T: DeserializeOwned>(data: &[u8]) -> anyhow::Result<T>;
+}
+
+///
+/// Media type of the data written by a serializer
+///
+/// Implemented by every [`PayloadSerializer`], serializers of non-serde types like
+/// [`ProtobufSerializer`](protobuf::ProtobufSerializer) implement it together with
+/// [`PayloadEncode`] and [`PayloadDecode`]
+///
+pub trait PayloadFormat {
+    ///
+    /// Media type of the serialized data
+    ///
+    fn content_type() -> Cow<'static, str>;
+}
+
+///
+/// Transform the data type `T` to bytes
+///
+pub trait PayloadEncode<T: ?Sized>: PayloadFormat {
+    ///
+    /// Transform data to bytes
+    ///
+    fn encode(data: &T) -> anyhow::Result<Vec<u8>>;
+}
+
+///
+/// Transform bytes to the data type `T`
+///
+pub trait PayloadDecode<T>: PayloadFormat {
+    ///
+    /// Transform bytes to data
+    ///
+    fn decode(data: &[u8]) -> anyhow::Result<T>;
+}
+
+impl<S: PayloadSerializer> PayloadFormat for S {
+    fn content_type() -> Cow<'static, str> {
+        <S as PayloadSerializer>::content_type()
+    }
+}
+
+impl<S: PayloadSerializer, T: Serialize> PayloadEncode<T> for S {
+    fn encode(data: &T) -> anyhow::Result<Vec<u8>> {
+        S::serialize_data(data)
+    }
+}
+
+impl<S: PayloadSerializer, T: DeserializeOwned> PayloadDecode<T> for S {
+    fn decode(data: &[u8]) -> anyhow::Result<T> {
+        S::deserialize_data(data)
+    }
+}
+
+///
+/// Type identifier and schema version written in the payload envelope
+///
+pub trait PayloadSchema {
+    ///
+    /// Type identifier
+    ///
+    fn schema_type() -> Cow<'static, str>;
+
+    ///
+    /// Schema version
+    ///
+    fn schema_version() -> u16 {
+        1
+    }
+}
+
+impl<T: PayloadSchema + ?Sized> PayloadSchema for &T {
+    fn schema_type() -> Cow<'static, str> {
+        T::schema_type()
+    }
+
+    fn schema_version() -> u16 {
+        T::schema_version()
+    }
+}
+
+impl<T: PayloadSchema> PayloadSchema for Vec<T> {
+    fn schema_type() -> Cow<'static, str> {
+        Cow::Owned(format!("{}[]", T::schema_type()))
+    }
+
+    fn schema_version() -> u16 {
+        T::schema_version()
+    }
+}
+
+///
+/// Data split in a public and a masked part
+///
+/// Usually derived with `#[derive(StreamsPayload)]`, marking the masked fields with `#[masked]`
+///
+pub trait StreamsPayload: Sized {
+    ///
+    /// Public part of the data
+    ///
+    type Public: Serialize + DeserializeOwned + PayloadSchema;
+
+    ///
+    /// Masked part of the data
+    ///
+    type Masked: Serialize + DeserializeOwned + PayloadSchema;
+
+    ///
+    /// Split the data in its public and masked parts
+    ///
+    fn split(&self) -> (Self::Public, Self::Masked);
+
+    ///
+    /// Rebuild the data from its public and masked parts
+    ///
+    fn join(public: Self::Public, masked: Self::Masked) -> Self;
+}
+
+///
+/// Payload
+///
+pub trait PacketPayload {
+    ///
+    /// Return the public payload data
+    ///
+    fn public_data(&self) -> &Bytes;
+    ///
+    /// Return the masked payload data
+    ///
+    fn masked_data(&self) -> &Bytes;
+}
+
+///
+/// Packet
+///
+pub struct Payload<S> {
+    public: Bytes,
+    masked: Bytes,
+    _marker: PhantomData<S>,
+}
+
+impl<S> Payload<S> {
+    ///
+    /// Unwrap Data
+    ///
+    /// The payload envelope must describe the type `T` serialized with `S`
+    ///
+    pub fn unwrap_data<T>(data: &Bytes) -> Result<T, PayloadError>
+    where
+        S: PayloadDecode<T>,
+        T: PayloadSchema,
+    {
+        Self::unwrap_data_with(data, &GroupKeyRing::new(), &TrustedKeys::new())
+    }
+
+    ///
+    /// Unwrap Data encrypted with a group key of the key ring or signed with a trusted key
+    ///
+    pub fn unwrap_data_with<T>(
+        data: &Bytes,
+        keys: &GroupKeyRing,
+        signers: &TrustedKeys,
+    ) -> Result<T, PayloadError>
+    where
+        S: PayloadDecode<T>,
+        T: PayloadSchema,
+    {
+        let (envelope, raw) = open(&data.0, keys, signers)?;
+        let raw = &raw[..];
+        if !envelope.is::<T>() || envelope.content_type != S::content_type() {
+            return Err(PayloadError::UnknownSchema {
+                type_id: envelope.type_id,
+                version: envelope.version,
+                content_type: envelope.content_type,
+            });
+        }
+        S::decode(raw).map_err(|source| PayloadError::Deserialize {
+            bytes: raw.to_vec(),
+            source,
+        })
+    }
+
+    ///
+    /// Unwrap the public and masked data and rebuild the split data
+    ///
+    pub fn unwrap_split<T>(public: &Bytes, masked: &Bytes) -> Result<T, PayloadError>
+    where
+        S: PayloadDecode<T::Public> + PayloadDecode<T::Masked>,
+        T: StreamsPayload,
+    {
+        Ok(T::join(
+            Self::unwrap_data(public)?,
+            Self::unwrap_data(masked)?,
+        ))
+    }
+
+    ///
+    /// Read the envelope of the data without decoding it
+    ///
+    pub fn envelope(data: &Bytes) -> Result<Envelope, PayloadError> {
+        Self::open(data).map(|(envelope, _)| envelope)
+    }
+
+    ///
+    /// Verify the signature of the data with the trusted keys, returns the signer or `None` when
+    /// the data is not signed
+    ///
+    pub fn signer(data: &Bytes, signers: &TrustedKeys) -> Result<Option<Signer>, PayloadError> {
+        let data = unseal(&data.0, &GroupKeyRing::new())?;
+        if !signature::is_signed(&data) {
+            return Ok(None);
+        }
+        signature::verify(&data, signers).map(|(signer, _)| Some(signer))
+    }
+
+    ///
+    /// Verify the digest of the data, returns the digest or `None` when the data has no digest
+    ///
+    /// The digest only depends on the serialized data, so it can be used as a dedup key. The
+    /// signature of signed data is verified with the trusted keys
+    ///
+    pub fn digest(data: &Bytes, signers: &TrustedKeys) -> Result<Option<[u8; 32]>, PayloadError> {
+        let keys = GroupKeyRing::new();
+        let data = unseal(&data.0, &keys)?;
+        let data = if signature::is_signed(&data) {
+            signature::verify(&data, signers)?.1
+        } else {
+            &data[..]
+        };
+        if !digest::has_digest(data) {
+            return Ok(None);
+        }
+        open(data, &keys, signers)?;
+        digest::split(data).map(|(digest, _)| Some(digest))
+    }
+
+    fn open(data: &Bytes) -> Result<(Envelope, Cow<'_, [u8]>), PayloadError> {
+        open(&data.0, &GroupKeyRing::new(), &TrustedKeys::new())
+    }
+}
+
+///
+/// Read the envelope of the data, either binary or a CloudEvent, returns the envelope and the
+/// wrapped data. Text payloads are decoded, data encrypted with a group key is decrypted with
+/// the keys of the key ring, the signature of signed data is verified with the trusted keys and
+/// the digest of the serialized data is verified
+///
+pub(crate) fn open<'a>(
+    data: &'a [u8],
+    keys: &GroupKeyRing,
+    signers: &TrustedKeys,
+) -> Result<(Envelope, Cow<'a, [u8]>), PayloadError> {
+    if data.is_empty() {
+        return Err(PayloadError::Empty);
+    }
+    if text::is_text(data) {
+        let decoded = text::decode(data)?;
+        return open(&decoded, keys, signers)
+            .map(|(envelope, raw)| (envelope, Cow::Owned(raw.into_owned())));
+    }
+    if encryption::is_encrypted(data) {
+        let plain = encryption::decrypt(keys, data)?;
+        return open(&plain, keys, signers)
+            .map(|(envelope, raw)| (envelope, Cow::Owned(raw.into_owned())));
+    }
+    if signature::is_signed(data) {
+        let (_, signed) = signature::verify(data, signers)?;
+        return open(signed, keys, signers);
+    }
+    if digest::has_digest(data) {
+        let (expected, enveloped) = digest::split(data)?;
+        let (envelope, raw) = open(enveloped, keys, signers)?;
+        digest::verify(&expected, &raw)?;
+        return Ok((envelope, raw));
+    }
+    if cloudevents::is_cloud_event(data) {
+        let (envelope, raw) = cloudevents::CloudEvent::parse(data)?.open()?;
+        return Ok((envelope, Cow::Owned(raw)));
+    }
+    Envelope::open(data).map(|(envelope, raw)| (envelope, Cow::Borrowed(raw)))
+}
+
+///
+/// Decode the text payloads and decrypt the data encrypted with a group key of the key ring
+///
+fn unseal<'a>(data: &'a [u8], keys: &GroupKeyRing) -> Result<Cow<'a, [u8]>, PayloadError> {
+    if text::is_text(data) {
+        let decoded = text::decode(data)?;
+        return unseal(&decoded, keys).map(|data| Cow::Owned(data.into_owned()));
+    }
+    if encryption::is_encrypted(data) {
+        return encryption::decrypt(keys, data).map(Cow::Owned);
+    }
+    Ok(Cow::Borrowed(data))
+}
+
+impl<S> PacketPayload for Payload<S> {
+    fn public_data(&self) -> &Bytes {
+        &self.public
+    }
+    fn masked_data(&self) -> &Bytes {
+        &self.masked
+    }
+}
+
+///
+/// Size of the public and masked data against the payload limit
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PayloadBudget {
+    /// Bytes used by the public data
+    pub public: usize,
+    /// Bytes used by the masked data
+    pub masked: usize,
+    /// Maximum bytes of public and masked data together
+    pub limit: usize,
+}
+
+impl PayloadBudget {
+    ///
+    /// Bytes used by the public and masked data together
+    ///
+    pub fn used(&self) -> usize {
+        self.public + self.masked
+    }
+
+    ///
+    /// Bytes left before reaching the limit, negative when the limit is exceeded
+    ///
+    pub fn headroom(&self) -> i64 {
+        self.limit as i64 - self.used() as i64
+    }
+
+    ///
+    /// Check if the data fits in the limit
+    ///
+    pub fn fits(&self) -> bool {
+        self.used() <= self.limit
+    }
+}
+
+impl fmt::Display for PayloadBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "public {} bytes + masked {} bytes = {} of {} bytes, headroom {} bytes",
+            self.public,
+            self.masked,
+            self.used(),
+            self.limit,
+            self.headroom()
+        )
+    }
+}
+
+///
+/// Payload Builder
+///
+pub struct PayloadBuilder<S> {
+    p_data: Vec<u8>,
+    m_data: Vec<u8>,
+    p_sealed: Vec<u8>,
+    m_sealed: Vec<u8>,
+    limit: usize,
+    cloud_event: Option<String>,
+    device_key: Option<DeviceKey>,
+    group_key: Option<(String, GroupKey)>,
+    with_digest: bool,
+    text_safe: bool,
+    clock: Box<dyn Clock>,
+    _marker: PhantomData<S>,
+}
+
+impl<S> Default for PayloadBuilder<S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<S> PayloadBuilder<S> {
+    ///
+    /// Create Instance
+    ///
+    pub fn new() -> Self {
+        PayloadBuilder {
+            p_data: Vec::new(),
+            m_data: Vec::new(),
+            p_sealed: Vec::new(),
+            m_sealed: Vec::new(),
+            limit: PAYLOAD_BYTES,
+            cloud_event: None,
+            device_key: None,
+            group_key: None,
+            with_digest: false,
+            text_safe: false,
+            clock: Box::new(SystemClock),
+            _marker: PhantomData,
+        }
+    }
+
+    ///
+    /// Create Instance wrapping the data in CloudEvents instead of the binary envelope,
+    /// `source` is usually the channel address
+    ///
+    pub fn cloud_events<A: Into<String>>(source: A) -> Self {
+        PayloadBuilder {
+            cloud_event: Some(source.into()),
+            ..Self::new()
+        }
+    }
+
+    ///
+    /// Maximum bytes of public and masked data checked by [`try_build`](Self::try_build),
+    /// Default: `PAYLOAD_BYTES`
+    ///
+    pub fn limit(&mut self, bytes: usize) -> &mut Self {
+        self.limit = bytes;
+        self
+    }
+
+    ///
+    /// Clock of the envelope timestamps of the data added after it, Default: `SystemClock`
+    ///
+    pub fn clock<C: Clock + 'static>(&mut self, clock: C) -> &mut Self {
+        self.clock = Box::new(clock);
+        self
+    }
+
+    ///
+    /// Current size of the public and masked data against the limit
+    ///
+    pub fn budget(&self) -> PayloadBudget {
+        PayloadBudget {
+            public: self.p_sealed.len(),
+            masked: self.m_sealed.len(),
+            limit: self.limit,
+        }
+    }
+
+    ///
+    /// Public Data
+    ///
+    pub fn public<T>(&mut self, data: &T) -> anyhow::Result<&mut Self>
+    where
+        S: PayloadEncode<T>,
+        T: PayloadSchema,
+    {
+        self.p_data = self.wrap(data)?;
+        self.seal()?;
+        Ok(self)
+    }
+
+    ///
+    /// Masked Data
+    ///
+    pub fn masked<T>(&mut self, data: &T) -> anyhow::Result<&mut Self>
+    where
+        S: PayloadEncode<T>,
+        T: PayloadSchema,
+    {
+        self.m_data = self.wrap(data)?;
+        self.seal()?;
+        Ok(self)
+    }
+
+    ///
+    /// Add the blake2b digest of the serialized data to the public and masked data, checked
+    /// when the data is unwrapped
+    ///
+    pub fn digest(&mut self) -> anyhow::Result<&mut Self> {
+        self.with_digest = true;
+        self.seal()?;
+        Ok(self)
+    }
+
+    ///
+    /// Sign the public and masked data with the device key
+    ///
+    pub fn sign(&mut self, key: &DeviceKey) -> anyhow::Result<&mut Self> {
+        self.device_key = Some(key.clone());
+        self.seal()?;
+        Ok(self)
+    }
+
+    ///
+    /// Encrypt the masked data with the named group key of the key ring, only the
+    /// subscribers holding the key can read it
+    ///
+    pub fn encrypt_masked(&mut self, keys: &GroupKeyRing, name: &str) -> anyhow::Result<&mut Self> {
+        let key = keys
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("Missing group key {}", name))?;
+        self.group_key = Some((name.to_string(), key.clone()));
+        self.seal()?;
+        Ok(self)
+    }
+
+    ///
+    /// Encode the public and masked data as URL-safe base64 text, for transports which only
+    /// carry printable ASCII
+    ///
+    pub fn text_safe(&mut self) -> anyhow::Result<&mut Self> {
+        self.text_safe = true;
+        self.seal()?;
+        Ok(self)
+    }
+
+    ///
+    /// Split the data in its public and masked parts
+    ///
+    pub fn split<T>(&mut self, data: &T) -> anyhow::Result<&mut Self>
+    where
+        S: PayloadEncode<T::Public> + PayloadEncode<T::Masked>,
+        T: StreamsPayload,
+    {
+        let (public, masked) = data.split();
+        self.public(&public)?.masked(&masked)
+    }
+
+    ///
+    /// Build
+    ///
+    pub fn build(&self) -> Payload<S> {
+        Payload {
+            public: Bytes(self.p_sealed.clone()),
+            masked: Bytes(self.m_sealed.clone()),
+            _marker: PhantomData,
+        }
+    }
+
+    ///
+    /// Build, checking the public and masked data fit in the limit
+    ///
+    pub fn try_build(&self) -> Result<Payload<S>, PayloadError> {
+        let budget = self.budget();
+        if !budget.fits() {
+            return Err(PayloadError::TooLarge(budget));
+        }
+        Ok(self.build())
+    }
+
+    fn seal(&mut self) -> anyhow::Result<()> {
+        let public = self.sign_data(&self.digest_data(&self.p_data)?)?;
+        let masked = self.sign_data(&self.digest_data(&self.m_data)?)?;
+        let masked = match &self.group_key {
+            Some((name, key)) if !masked.is_empty() => encryption::encrypt(name, key, &masked)?,
+            _ => masked,
+        };
+        self.p_sealed = self.encode_text(public);
+        self.m_sealed = self.encode_text(masked);
+        Ok(())
+    }
+
+    fn encode_text(&self, data: Vec<u8>) -> Vec<u8> {
+        if !self.text_safe || data.is_empty() {
+            return data;
+        }
+        text::encode(&data)
+    }
+
+    fn digest_data(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        if !self.with_digest || data.is_empty() {
+            return Ok(data.to_vec());
+        }
+        let (_, raw) = open(data, &GroupKeyRing::new(), &TrustedKeys::new())?;
+        Ok(digest::wrap(&raw, data))
+    }
+
+    fn sign_data(&self, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+        match &self.device_key {
+            Some(key) if !data.is_empty() => signature::sign(key, data),
+            _ => Ok(data.to_vec()),
+        }
+    }
+
+    fn wrap<T>(&self, data: &T) -> anyhow::Result<Vec<u8>>
+    where
+        S: PayloadEncode<T>,
+        T: PayloadSchema,
+    {
+        let envelope = Envelope::new_at::<T, S>(self.clock.now());
+        let raw = S::encode(data)?;
+        match &self.cloud_event {
+            Some(source) => cloudevents::wrap(&envelope, source, &raw),
+            None => envelope.wrap(&raw),
+        }
+    }
+}
+
+pub mod batch;
+pub mod chunk;
+pub mod cloudevents;
+pub mod compress;
+pub mod digest;
+pub mod encryption;
+pub mod envelope;
+mod error;
+pub mod json;
+pub mod registry;
+pub mod senml;
+pub mod signature;
+pub mod text;
+pub mod timeseries;
+pub mod validation;
+
+#[cfg(feature = "cbor")]
+pub mod cbor;
+
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+
+#[cfg(feature = "bincode")]
+pub mod bincode;
+
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+
+#[cfg(test)]
+mod tests {
+    use super::{envelope::Envelope, json, PacketPayload, StreamsPayload};
+
+    #[derive(Debug, Clone, PartialEq, StreamsPayload)]
+    #[streams_payload(type_id = "reading", version = 2)]
+    struct Reading {
+        ts: i64,
+        temperature: f32,
+        #[masked]
+        desc: String,
+    }
+
+    #[test]
+    fn split_and_join_derived_payload() {
+        let reading = Reading {
+            ts: 1_617_301_189,
+            temperature: 21.5,
+            desc: "Boiler room".to_string(),
+        };
+        let payload = json::PayloadBuilder::new().split(&reading).unwrap().build();
+
+        let public = Envelope::open(&payload.public_data().0).unwrap();
+        assert_eq!(public.0.type_id, "reading.public");
+        assert_eq!(public.0.version, 2);
+        assert!(!String::from_utf8_lossy(public.1).contains("Boiler room"));
+
+        let joined: Reading =
+            json::Payload::unwrap_split(payload.public_data(), payload.masked_data()).unwrap();
+        assert_eq!(joined, reading);
+    }
+}
@@ -0,0 +1,50 @@
+//!
+//! MessagePack Payload Serialization module
+//!
+use super::PayloadSerializer;
+use serde::{de::DeserializeOwned, Serialize};
+use std::borrow::Cow;
+
+///
+/// Implementation of MessagePack Serialize
+///
+/// Structs are written as arrays, without field names, to keep the packets small
+///
+pub struct MsgPackSerializer;
+
+impl PayloadSerializer for MsgPackSerializer {
+    fn content_type() -> Cow<'static, str> {
+        Cow::Borrowed("application/msgpack")
+    }
+
+    fn serialize_data<T: Serialize>(data: &T) -> anyhow::Result<Vec<u8>> {
+        rmp_serde::to_vec(data).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+
+    fn deserialize_data<T: DeserializeOwned>(data: &[u8]) -> anyhow::Result<T> {
+        rmp_serde::from_slice(data).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+}
+
+/// Payload MessagePack
+///
+pub type Payload = super::Payload<MsgPackSerializer>;
+
+/// Payload Builder in MessagePack Format
+///
+pub type PayloadBuilder = super::PayloadBuilder<MsgPackSerializer>;
+
+#[cfg(test)]
+mod tests {
+    use super::{Payload, PayloadBuilder};
+    use crate::{payload::PacketPayload, sample::StreamsData};
+
+    #[test]
+    fn roundtrip_streams_data() {
+        let data = StreamsData::default();
+        let payload = PayloadBuilder::new().public(&data).unwrap().build();
+
+        let public: StreamsData = Payload::unwrap_data(payload.public_data()).unwrap();
+        assert_eq!(public, data);
+    }
+}
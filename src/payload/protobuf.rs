@@ -0,0 +1,68 @@
+//!
+//! Protobuf Payload Serialization module
+//!
+//! Protobuf messages are not serde types, so the serializer implements [`PayloadEncode`] and
+//! [`PayloadDecode`] for every [`prost::Message`] instead of [`PayloadSerializer`](super::PayloadSerializer)
+//!
+use super::{PayloadDecode, PayloadEncode, PayloadFormat};
+use prost::Message;
+use std::borrow::Cow;
+
+///
+/// Implementation of Protobuf Serialize
+///
+pub struct ProtobufSerializer;
+
+impl PayloadFormat for ProtobufSerializer {
+    fn content_type() -> Cow<'static, str> {
+        Cow::Borrowed("application/x-protobuf")
+    }
+}
+
+impl<T: Message> PayloadEncode<T> for ProtobufSerializer {
+    fn encode(data: &T) -> anyhow::Result<Vec<u8>> {
+        let mut out = Vec::with_capacity(data.encoded_len());
+        data.encode(&mut out)
+            .map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+        Ok(out)
+    }
+}
+
+impl<T: Message + Default> PayloadDecode<T> for ProtobufSerializer {
+    fn decode(data: &[u8]) -> anyhow::Result<T> {
+        T::decode(data).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+}
+
+/// Payload Protobuf
+///
+pub type Payload = super::Payload<ProtobufSerializer>;
+
+/// Payload Builder in Protobuf Format
+///
+pub type PayloadBuilder = super::PayloadBuilder<ProtobufSerializer>;
+
+#[cfg(test)]
+mod tests {
+    use super::{Payload, PayloadBuilder};
+    use crate::{
+        payload::PacketPayload,
+        sample::{self, proto, StreamsData},
+    };
+    use std::convert::TryFrom;
+
+    #[test]
+    fn roundtrip_streams_data() {
+        let data = StreamsData::default();
+        let payload = PayloadBuilder::new()
+            .masked(&proto::StreamsData::try_from(&data).unwrap())
+            .unwrap()
+            .build();
+
+        let masked: proto::StreamsData = Payload::unwrap_data(payload.masked_data()).unwrap();
+        assert_eq!(StreamsData::from(masked), data);
+
+        let decoded = sample::registry().decode(payload.masked_data()).unwrap();
+        assert_eq!(decoded.envelope.content_type, "application/x-protobuf");
+    }
+}
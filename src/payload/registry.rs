@@ -0,0 +1,319 @@
+//!
+//! Payload Decoder Registry module
+//!
+//! Decodes payloads to a typed message enum, choosing the decoder by the type id, schema
+//! version and content type of the payload envelope.
+//!
+//! ```ignore
+//! enum Message {
+//!     Reading(StreamsData),
+//!     Status(DeviceStatus),
+//! }
+//!
+//! let mut registry = DecoderRegistry::new();
+//! registry
+//!     .register::<StreamsData, JsonSerializer, _>(Message::Reading)
+//!     .register::<DeviceStatus, JsonSerializer, _>(Message::Status);
+//!
+//! match registry.decode(&public)? {
+//!     Some(Decoded { message: Message::Reading(data), .. }) => println!("{:?}", data),
+//!     ...
+//! }
+//! ```
+//!
+//! JSON payloads are validated against the schemas added with
+//! [`validate_with`](DecoderRegistry::validate_with) before they are decoded, the data
+//! encrypted with a group key is decrypted with the key ring added with
+//! [`decrypt_with`](DecoderRegistry::decrypt_with) and the signed data is only accepted from the
+//! keys added with [`verify_with`](DecoderRegistry::verify_with)
+//!
+use super::{
+    encryption::GroupKeyRing,
+    envelope::Envelope,
+    signature::TrustedKeys,
+    validation::{SchemaSet, ValidationReport},
+    PayloadDecode, PayloadError, PayloadSchema,
+};
+use iota_streams::ddml::types::Bytes;
+use std::collections::HashMap;
+
+type Decoder<M> = Box<dyn Fn(&[u8]) -> anyhow::Result<M>>;
+
+///
+/// Decoded Message
+///
+#[derive(Debug)]
+pub struct Decoded<M> {
+    /// Envelope of the payload
+    pub envelope: Envelope,
+    /// Decoded message
+    pub message: M,
+    /// Schema validation of the payload, `None` when no schema applies
+    pub validation: Option<ValidationReport>,
+}
+
+///
+/// Registry of payload decoders
+///
+pub struct DecoderRegistry<M> {
+    decoders: HashMap<(String, u16, String), Decoder<M>>,
+    schemas: SchemaSet,
+    keys: GroupKeyRing,
+    signers: TrustedKeys,
+}
+
+impl<M> Default for DecoderRegistry<M> {
+    fn default() -> Self {
+        DecoderRegistry {
+            decoders: HashMap::new(),
+            schemas: SchemaSet::new(),
+            keys: GroupKeyRing::new(),
+            signers: TrustedKeys::new(),
+        }
+    }
+}
+
+impl<M> DecoderRegistry<M> {
+    ///
+    /// Create Instance
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Register the decoder of the data type `T` serialized with `S`
+    ///
+    /// `map` builds the message from the decoded data. Registering the same type, version and
+    /// content type again replaces the previous decoder
+    ///
+    pub fn register<T, S, F>(&mut self, map: F) -> &mut Self
+    where
+        T: PayloadSchema,
+        S: PayloadDecode<T>,
+        F: Fn(T) -> M + 'static,
+    {
+        self.decoders.insert(
+            (
+                T::schema_type().into_owned(),
+                T::schema_version(),
+                S::content_type().into_owned(),
+            ),
+            Box::new(move |raw| S::decode(raw).map(&map)),
+        );
+        self
+    }
+
+    ///
+    /// Validate the JSON payloads against the schemas before decoding them, payloads which do
+    /// not match are rejected with [`PayloadError::SchemaViolation`]
+    ///
+    pub fn validate_with(&mut self, schemas: SchemaSet) -> &mut Self {
+        self.schemas = schemas;
+        self
+    }
+
+    ///
+    /// Decrypt the data encrypted with a group key of the key ring, the data encrypted with
+    /// another key is rejected with [`PayloadError::UnknownKey`]
+    ///
+    pub fn decrypt_with(&mut self, keys: GroupKeyRing) -> &mut Self {
+        self.keys = keys;
+        self
+    }
+
+    ///
+    /// Verify the signed data with the trusted keys, the data signed with another key is
+    /// rejected with [`PayloadError::UntrustedSigner`]
+    ///
+    pub fn verify_with(&mut self, signers: TrustedKeys) -> &mut Self {
+        self.signers = signers;
+        self
+    }
+
+    ///
+    /// Check if there is a decoder for the envelope
+    ///
+    pub fn supports(&self, envelope: &Envelope) -> bool {
+        self.decoders.contains_key(&Self::key(envelope))
+    }
+
+    fn key(envelope: &Envelope) -> (String, u16, String) {
+        (
+            envelope.type_id.clone(),
+            envelope.version,
+            envelope.content_type.clone(),
+        )
+    }
+
+    ///
+    /// Decode the payload data
+    ///
+    pub fn decode(&self, data: &Bytes) -> Result<Decoded<M>, PayloadError> {
+        self.decode_from(None, data)
+    }
+
+    ///
+    /// Decode the payload data published in the channel, validated against the schemas of its
+    /// type and of the channel
+    ///
+    pub fn decode_in(&self, channel: &str, data: &Bytes) -> Result<Decoded<M>, PayloadError> {
+        self.decode_from(Some(channel), data)
+    }
+
+    fn decode_from(&self, channel: Option<&str>, data: &Bytes) -> Result<Decoded<M>, PayloadError> {
+        let (envelope, raw) = super::open(&data.0, &self.keys, &self.signers)?;
+        let raw = &raw[..];
+
+        let decoder = match self.decoders.get(&Self::key(&envelope)) {
+            Some(decoder) => decoder,
+            None => {
+                return Err(PayloadError::UnknownSchema {
+                    type_id: envelope.type_id,
+                    version: envelope.version,
+                    content_type: envelope.content_type,
+                })
+            }
+        };
+
+        let validation = self.schemas.validate(channel, &envelope, raw)?;
+        if let Some(report) = validation.as_ref().filter(|report| !report.is_valid()) {
+            return Err(PayloadError::SchemaViolation(report.clone()));
+        }
+
+        let message = decoder(raw).map_err(|source| PayloadError::Deserialize {
+            bytes: raw.to_vec(),
+            source,
+        })?;
+        Ok(Decoded {
+            envelope,
+            message,
+            validation,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DecoderRegistry;
+    use crate::{
+        payload::{
+            encryption::{GroupKey, GroupKeyRing},
+            json::JsonSerializer,
+            PacketPayload, PayloadBuilder, PayloadError, PayloadSchema,
+        },
+        sample::{self, StreamsData},
+    };
+    use serde::{Deserialize, Serialize};
+    use std::borrow::Cow;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Status {
+        online: bool,
+    }
+
+    impl PayloadSchema for Status {
+        fn schema_type() -> Cow<'static, str> {
+            Cow::Borrowed("status")
+        }
+
+        fn schema_version() -> u16 {
+            2
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum Message {
+        Reading(StreamsData),
+        Status(Status),
+    }
+
+    fn registry() -> DecoderRegistry<Message> {
+        let mut registry = DecoderRegistry::new();
+        registry
+            .register::<StreamsData, JsonSerializer, _>(Message::Reading)
+            .register::<Status, JsonSerializer, _>(Message::Status);
+        registry
+    }
+
+    #[test]
+    fn decode_registered_types() {
+        let data = StreamsData::default();
+        let payload = PayloadBuilder::<JsonSerializer>::new()
+            .public(&data)
+            .unwrap()
+            .masked(&Status { online: true })
+            .unwrap()
+            .build();
+
+        let registry = registry();
+        let public = registry.decode(payload.public_data()).unwrap();
+        let masked = registry.decode(payload.masked_data()).unwrap();
+
+        assert_eq!(public.envelope.type_id, "streams-data");
+        assert_eq!(public.message, Message::Reading(data));
+        assert_eq!(masked.envelope.version, 2);
+        assert_eq!(masked.message, Message::Status(Status { online: true }));
+    }
+
+    #[test]
+    fn reject_unknown_types() {
+        let payload = PayloadBuilder::<JsonSerializer>::new()
+            .public(&vec![StreamsData::default()])
+            .unwrap()
+            .build();
+
+        assert!(matches!(
+            registry().decode(payload.public_data()),
+            Err(PayloadError::UnknownSchema { .. })
+        ));
+        assert!(matches!(
+            registry().decode(payload.masked_data()),
+            Err(PayloadError::Empty)
+        ));
+    }
+
+    #[test]
+    fn decrypt_with_key_ring() {
+        let mut keys = GroupKeyRing::new();
+        keys.insert("operators", GroupKey::generate());
+        let payload = PayloadBuilder::<JsonSerializer>::new()
+            .masked(&Status { online: true })
+            .unwrap()
+            .encrypt_masked(&keys, "operators")
+            .unwrap()
+            .build();
+
+        let mut registry = registry();
+        assert!(matches!(
+            registry.decode(payload.masked_data()),
+            Err(PayloadError::UnknownKey(_))
+        ));
+        registry.decrypt_with(keys);
+        let masked = registry.decode(payload.masked_data()).unwrap();
+        assert_eq!(masked.message, Message::Status(Status { online: true }));
+    }
+
+    #[test]
+    fn validate_json_payloads() {
+        let payload = PayloadBuilder::<JsonSerializer>::new()
+            .public(&StreamsData::new("Boiler room", 21.5, 101_325.0))
+            .unwrap()
+            .masked(&StreamsData::new("Boiler room", 1.0e6, 101_325.0))
+            .unwrap()
+            .build();
+
+        let mut registry = registry();
+        registry.validate_with(sample::schemas());
+        let public = registry.decode(payload.public_data()).unwrap();
+        assert!(public.validation.unwrap().is_valid());
+
+        match registry.decode_in("channel", payload.masked_data()) {
+            Err(PayloadError::SchemaViolation(report)) => {
+                assert_eq!(report.type_id, "streams-data");
+                assert_eq!(report.violations[0].path, "/temperature");
+            }
+            other => panic!("Unexpected result {:?}", other.map(|d| d.message)),
+        }
+    }
+}
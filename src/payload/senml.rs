@@ -0,0 +1,494 @@
+//!
+//! SenML Payload Serialization module
+//!
+//! Sensor Measurement Lists ([RFC 8428](https://tools.ietf.org/html/rfc8428)) packs in JSON, and
+//! in CBOR with the `cbor` feature. JSON records use the field labels (`bn`, `bt`, `v`, ...)
+//! and CBOR records the integer keys of the RFC. The Data Value is a URL-safe base64 string
+//! without padding in JSON and a byte string in CBOR.
+//!
+use super::{PayloadSchema, PayloadSerializer};
+use base64::URL_SAFE_NO_PAD;
+use serde::{
+    de::{self, DeserializeOwned, Deserializer, IgnoredAny, MapAccess, Visitor},
+    ser::{SerializeMap, Serializer},
+    Deserialize, Serialize,
+};
+use std::{borrow::Cow, fmt};
+
+///
+/// Labels and CBOR keys of the record fields, in the order of the `SenmlRecord` fields
+///
+const FIELDS: [(&str, i8); 13] = [
+    ("bn", -2),
+    ("bt", -3),
+    ("bu", -4),
+    ("bv", -5),
+    ("n", 0),
+    ("u", 1),
+    ("v", 2),
+    ("vs", 3),
+    ("vb", 4),
+    ("vd", 8),
+    ("s", 5),
+    ("t", 6),
+    ("ut", 7),
+];
+
+///
+/// SenML Record
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SenmlRecord {
+    /// Base Name, prefix of the names of this and the following records
+    pub base_name: Option<String>,
+    /// Base Time in seconds, added to the time of this and the following records
+    pub base_time: Option<f64>,
+    /// Base Unit, unit of this and the following records without unit
+    pub base_unit: Option<String>,
+    /// Base Value, added to the value of this and the following records
+    pub base_value: Option<f64>,
+    /// Name
+    pub name: Option<String>,
+    /// Unit
+    pub unit: Option<String>,
+    /// Numeric Value
+    pub value: Option<f64>,
+    /// String Value
+    pub string_value: Option<String>,
+    /// Boolean Value
+    pub bool_value: Option<bool>,
+    /// Data Value
+    pub data_value: Option<Vec<u8>>,
+    /// Sum of the values
+    pub sum: Option<f64>,
+    /// Time in seconds
+    pub time: Option<f64>,
+    /// Maximum seconds before the next update
+    pub update_time: Option<f64>,
+}
+
+impl SenmlRecord {
+    fn fields<'a>(&'a self) -> [Option<Field<'a>>; 13] {
+        let text = |v: &'a Option<String>| v.as_deref().map(Field::Text);
+        let number = |v: &Option<f64>| v.map(Field::Number);
+        [
+            text(&self.base_name),
+            number(&self.base_time),
+            text(&self.base_unit),
+            number(&self.base_value),
+            text(&self.name),
+            text(&self.unit),
+            number(&self.value),
+            text(&self.string_value),
+            self.bool_value.map(Field::Bool),
+            self.data_value.as_deref().map(Field::Data),
+            number(&self.sum),
+            number(&self.time),
+            number(&self.update_time),
+        ]
+    }
+
+    fn read_field<'de, A: MapAccess<'de>>(
+        &mut self,
+        field: usize,
+        map: &mut A,
+    ) -> Result<(), A::Error> {
+        match field {
+            0 => self.base_name = Some(map.next_value()?),
+            1 => self.base_time = Some(map.next_value()?),
+            2 => self.base_unit = Some(map.next_value()?),
+            3 => self.base_value = Some(map.next_value()?),
+            4 => self.name = Some(map.next_value()?),
+            5 => self.unit = Some(map.next_value()?),
+            6 => self.value = Some(map.next_value()?),
+            7 => self.string_value = Some(map.next_value()?),
+            8 => self.bool_value = Some(map.next_value()?),
+            9 => self.data_value = Some(map.next_value::<DataValue>()?.0),
+            10 => self.sum = Some(map.next_value()?),
+            11 => self.time = Some(map.next_value()?),
+            _ => self.update_time = Some(map.next_value()?),
+        }
+        Ok(())
+    }
+}
+
+///
+/// Value of a record field
+///
+enum Field<'a> {
+    Text(&'a str),
+    Number(f64),
+    Bool(bool),
+    Data(&'a [u8]),
+}
+
+impl Serialize for Field<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Field::Text(v) => serializer.serialize_str(v),
+            Field::Number(v) => serializer.serialize_f64(*v),
+            Field::Bool(v) => serializer.serialize_bool(*v),
+            Field::Data(v) if serializer.is_human_readable() => {
+                serializer.serialize_str(&base64::encode_config(v, URL_SAFE_NO_PAD))
+            }
+            Field::Data(v) => serializer.serialize_bytes(v),
+        }
+    }
+}
+
+///
+/// Data Value read from a base64 string or a byte string
+///
+struct DataValue(Vec<u8>);
+
+impl<'de> Deserialize<'de> for DataValue {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(DataValueVisitor)
+    }
+}
+
+struct DataValueVisitor;
+
+impl<'de> Visitor<'de> for DataValueVisitor {
+    type Value = DataValue;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SenML data value")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        // Padding is omitted by the RFC, but accepted
+        base64::decode_config(v.trim_end_matches('='), URL_SAFE_NO_PAD)
+            .map(DataValue)
+            .map_err(|e| E::custom(format!("Invalid SenML data value: {}", e)))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        Ok(DataValue(v.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+        Ok(DataValue(v))
+    }
+}
+
+impl Serialize for SenmlRecord {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let labels = serializer.is_human_readable();
+        let fields = self.fields();
+        let mut map = serializer.serialize_map(Some(fields.iter().flatten().count()))?;
+        for ((label, key), value) in FIELDS.iter().zip(fields.iter()) {
+            if let Some(value) = value {
+                if labels {
+                    map.serialize_entry(label, value)?;
+                } else {
+                    map.serialize_entry(key, value)?;
+                }
+            }
+        }
+        map.end()
+    }
+}
+
+///
+/// Key of a record field, either its label or its CBOR key
+///
+struct FieldKey(Option<usize>);
+
+impl<'de> Deserialize<'de> for FieldKey {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(FieldKeyVisitor)
+    }
+}
+
+struct FieldKeyVisitor;
+
+impl<'de> Visitor<'de> for FieldKeyVisitor {
+    type Value = FieldKey;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SenML field label or key")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        // Fields ending with `_` must be understood by the receiver
+        if v.ends_with('_') {
+            return Err(E::custom(format!("Unsupported SenML field {}", v)));
+        }
+        Ok(FieldKey(FIELDS.iter().position(|(label, _)| *label == v)))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(FieldKey(
+            FIELDS.iter().position(|(_, key)| *key as i64 == v),
+        ))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        Ok(FieldKey(
+            FIELDS.iter().position(|(_, key)| *key as u64 == v),
+        ))
+    }
+}
+
+impl<'de> Deserialize<'de> for SenmlRecord {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(RecordVisitor)
+    }
+}
+
+struct RecordVisitor;
+
+impl<'de> Visitor<'de> for RecordVisitor {
+    type Value = SenmlRecord;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SenML record")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut record = SenmlRecord::default();
+        while let Some(FieldKey(field)) = map.next_key()? {
+            match field {
+                Some(field) => record.read_field(field, &mut map)?,
+                None => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(record)
+    }
+}
+
+///
+/// SenML Pack
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct SenmlPack {
+    /// Records of the pack
+    pub records: Vec<SenmlRecord>,
+}
+
+impl SenmlPack {
+    ///
+    /// Create Instance
+    ///
+    pub fn new(records: Vec<SenmlRecord>) -> Self {
+        SenmlPack { records }
+    }
+
+    ///
+    /// Resolve the records applying the base fields, the resolved records have no base fields
+    ///
+    pub fn resolve(&self) -> Vec<SenmlRecord> {
+        let mut base = SenmlRecord::default();
+        self.records
+            .iter()
+            .map(|record| {
+                base.base_name = record.base_name.clone().or(base.base_name.take());
+                base.base_time = record.base_time.or(base.base_time);
+                base.base_unit = record.base_unit.clone().or(base.base_unit.take());
+                base.base_value = record.base_value.or(base.base_value);
+
+                SenmlRecord {
+                    base_name: None,
+                    base_time: None,
+                    base_unit: None,
+                    base_value: None,
+                    name: Some(format!(
+                        "{}{}",
+                        base.base_name.as_deref().unwrap_or_default(),
+                        record.name.as_deref().unwrap_or_default()
+                    )),
+                    unit: record.unit.clone().or_else(|| base.base_unit.clone()),
+                    value: record
+                        .value
+                        .map(|v| v + base.base_value.unwrap_or_default()),
+                    time: Some(
+                        record.time.unwrap_or_default() + base.base_time.unwrap_or_default(),
+                    ),
+                    ..record.clone()
+                }
+            })
+            .collect()
+    }
+
+    ///
+    /// Find a resolved record by its full name
+    ///
+    pub fn find(&self, name: &str) -> Option<SenmlRecord> {
+        self.resolve()
+            .into_iter()
+            .find(|record| record.name.as_deref() == Some(name))
+    }
+}
+
+impl PayloadSchema for SenmlPack {
+    fn schema_type() -> Cow<'static, str> {
+        Cow::Borrowed("senml")
+    }
+}
+
+///
+/// Implementation of SenML JSON Serialize, the data must be a SenML pack
+///
+pub struct SenmlJsonSerializer;
+
+impl PayloadSerializer for SenmlJsonSerializer {
+    fn content_type() -> Cow<'static, str> {
+        Cow::Borrowed("application/senml+json")
+    }
+
+    fn serialize_data<T: Serialize>(data: &T) -> anyhow::Result<Vec<u8>> {
+        let raw = serde_json::to_vec(data).map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+        serde_json::from_slice::<SenmlPack>(&raw)
+            .map_err(|e| anyhow::anyhow!("Data is not a SenML pack: {:#?}", e))?;
+        Ok(raw)
+    }
+
+    fn deserialize_data<T: DeserializeOwned>(data: &[u8]) -> anyhow::Result<T> {
+        serde_json::from_slice(data).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+}
+
+///
+/// Implementation of SenML CBOR Serialize, the data must be a SenML pack
+///
+#[cfg(feature = "cbor")]
+pub struct SenmlCborSerializer;
+
+#[cfg(feature = "cbor")]
+impl PayloadSerializer for SenmlCborSerializer {
+    fn content_type() -> Cow<'static, str> {
+        Cow::Borrowed("application/senml+cbor")
+    }
+
+    fn serialize_data<T: Serialize>(data: &T) -> anyhow::Result<Vec<u8>> {
+        let raw = serde_cbor::to_vec(data).map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+        serde_cbor::from_slice::<SenmlPack>(&raw)
+            .map_err(|e| anyhow::anyhow!("Data is not a SenML pack: {:#?}", e))?;
+        Ok(raw)
+    }
+
+    fn deserialize_data<T: DeserializeOwned>(data: &[u8]) -> anyhow::Result<T> {
+        serde_cbor::from_slice(data).map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+}
+
+/// Payload SenML JSON
+///
+pub type Payload = super::Payload<SenmlJsonSerializer>;
+
+/// Payload Builder in SenML JSON Format
+///
+pub type PayloadBuilder = super::PayloadBuilder<SenmlJsonSerializer>;
+
+/// Payload SenML CBOR
+///
+#[cfg(feature = "cbor")]
+pub type CborPayload = super::Payload<SenmlCborSerializer>;
+
+/// Payload Builder in SenML CBOR Format
+///
+#[cfg(feature = "cbor")]
+pub type CborPayloadBuilder = super::PayloadBuilder<SenmlCborSerializer>;
+
+#[cfg(test)]
+mod tests {
+    use super::{Payload, PayloadBuilder, SenmlJsonSerializer, SenmlPack, SenmlRecord};
+    use crate::{
+        payload::{PacketPayload, PayloadSerializer},
+        sample::StreamsData,
+    };
+    use std::{collections::HashMap, convert::TryFrom};
+
+    #[test]
+    fn roundtrip_streams_data() {
+        let data = StreamsData::new("Boiler room", 21.5, 101_325.0);
+        let pack = SenmlPack::from(&data);
+        let payload = PayloadBuilder::new().public(&pack).unwrap().build();
+
+        let raw = String::from_utf8_lossy(&payload.public_data().0).to_string();
+        assert!(raw.contains(r#""bn":"streams-data:""#));
+        assert!(raw.contains(r#""n":"temperature","u":"Cel","v":21.5"#));
+
+        let public: SenmlPack = Payload::unwrap_data(payload.public_data()).unwrap();
+        assert_eq!(public, pack);
+        let temperature = public.find("streams-data:temperature").unwrap();
+        assert_eq!(temperature.value, Some(21.5));
+        assert_eq!(temperature.time, pack.records[0].base_time);
+
+        let rebuilt = SenmlPack::from(&StreamsData::try_from(&public).unwrap());
+        assert_eq!(rebuilt.records[1..], pack.records[1..]);
+        assert_eq!(rebuilt.records[0].value, pack.records[0].value);
+        let skew = rebuilt.records[0].base_time.unwrap() - pack.records[0].base_time.unwrap();
+        assert!(skew.abs() < 1e-6);
+    }
+
+    #[test]
+    fn data_value_base64() {
+        let pack = SenmlPack::new(vec![SenmlRecord {
+            name: Some("firmware".to_string()),
+            data_value: Some(vec![0xFB, 0xFF, 0x00]),
+            ..Default::default()
+        }]);
+        let raw = SenmlJsonSerializer::serialize_data(&pack).unwrap();
+        assert_eq!(
+            String::from_utf8(raw.clone()).unwrap(),
+            r#"[{"n":"firmware","vd":"-_8A"}]"#
+        );
+        let read: SenmlPack = SenmlJsonSerializer::deserialize_data(&raw).unwrap();
+        assert_eq!(read, pack);
+    }
+
+    #[test]
+    fn reject_other_data() {
+        assert!(SenmlJsonSerializer::serialize_data(&StreamsData::default()).is_err());
+        let unsupported: HashMap<_, _> = [("n", "x"), ("bn_", "y")].iter().cloned().collect();
+        assert!(SenmlJsonSerializer::serialize_data(&vec![unsupported]).is_err());
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor_integer_keys() {
+        use super::{CborPayload, CborPayloadBuilder};
+        use crate::payload::envelope::Envelope;
+        use serde_cbor::Value;
+
+        let pack = SenmlPack::from(&StreamsData::new("Boiler room", 21.5, 101_325.0));
+        let payload = CborPayloadBuilder::new().masked(&pack).unwrap().build();
+
+        let (_, raw) = Envelope::open(&payload.masked_data().0).unwrap();
+        match serde_cbor::from_slice::<Value>(raw).unwrap() {
+            Value::Array(records) => match &records[0] {
+                Value::Map(fields) => {
+                    assert!(fields.contains_key(&Value::Integer(-2)));
+                    assert!(fields.contains_key(&Value::Integer(2)));
+                }
+                other => panic!("Unexpected record {:?}", other),
+            },
+            other => panic!("Unexpected pack {:?}", other),
+        }
+
+        let masked: SenmlPack = CborPayload::unwrap_data(payload.masked_data()).unwrap();
+        assert_eq!(masked, pack);
+
+        let data = SenmlPack::new(vec![SenmlRecord {
+            data_value: Some(vec![0xFB, 0xFF, 0x00]),
+            ..Default::default()
+        }]);
+        let raw = serde_cbor::to_vec(&data).unwrap();
+        match serde_cbor::from_slice::<Value>(&raw).unwrap() {
+            Value::Array(records) => match &records[0] {
+                Value::Map(fields) => assert_eq!(
+                    fields[&Value::Integer(8)],
+                    Value::Bytes(vec![0xFB, 0xFF, 0x00])
+                ),
+                other => panic!("Unexpected record {:?}", other),
+            },
+            other => panic!("Unexpected pack {:?}", other),
+        }
+        assert_eq!(serde_cbor::from_slice::<SenmlPack>(&raw).unwrap(), data);
+    }
+}
@@ -0,0 +1,318 @@
+//!
+//! Payload Signature module
+//!
+//! Payloads signed with an Ed25519 device key, independent of the channel keys. The signature,
+//! the key id and the public key travel with the data, so the provenance of the data can be
+//! verified after it is re-published or exported out of the channel.
+//!
+//! | magic | key id        | public key | signature | signed data |
+//! |-------|---------------|------------|-----------|-------------|
+//! | 2     | u8 len + utf8 | 32         | 64        | ...         |
+//!
+//! The signature covers the header and the signed data, it is verified when the payload is
+//! unwrapped. The public key in the header is chosen by the sender, so the data is only accepted
+//! when its key id is one of the [`TrustedKeys`] and the public key is the trusted one
+//!
+use super::PayloadError;
+use ed25519_dalek::{
+    Keypair, PublicKey, Signature, Signer as _, Verifier as _, KEYPAIR_LENGTH, PUBLIC_KEY_LENGTH,
+    SIGNATURE_LENGTH,
+};
+use std::{collections::HashMap, convert::TryFrom, fmt};
+use zeroize::Zeroizing;
+
+///
+/// Magic bytes of the signed data
+///
+pub const MAGIC: [u8; 2] = [0x5E, 0x01];
+
+///
+/// Ed25519 Device Key
+///
+pub struct DeviceKey {
+    id: String,
+    keypair: Keypair,
+}
+
+impl DeviceKey {
+    ///
+    /// Generate a random key
+    ///
+    pub fn generate<I: Into<String>>(id: I) -> Self {
+        DeviceKey {
+            id: id.into(),
+            keypair: Keypair::generate(&mut rand::rngs::OsRng),
+        }
+    }
+
+    ///
+    /// Create the key from the hex representation of its secret and public key
+    ///
+    pub fn from_hex<I: Into<String>>(id: I, value: &str) -> anyhow::Result<Self> {
+        let mut bytes = Zeroizing::new([0u8; KEYPAIR_LENGTH]);
+        hex::decode_to_slice(value, &mut *bytes).map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+        Ok(DeviceKey {
+            id: id.into(),
+            keypair: Keypair::from_bytes(&*bytes).map_err(|e| anyhow::anyhow!("{:#?}", e))?,
+        })
+    }
+
+    ///
+    /// Hex representation of the secret and public key
+    ///
+    pub fn to_hex(&self) -> Zeroizing<String> {
+        let bytes = Zeroizing::new(self.keypair.to_bytes());
+        Zeroizing::new(hex::encode(&bytes[..]))
+    }
+
+    ///
+    /// Key identifier
+    ///
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    ///
+    /// Public key
+    ///
+    pub fn public_key(&self) -> [u8; PUBLIC_KEY_LENGTH] {
+        self.keypair.public.to_bytes()
+    }
+}
+
+impl Clone for DeviceKey {
+    fn clone(&self) -> Self {
+        DeviceKey {
+            id: self.id.clone(),
+            // The bytes of a valid keypair are always valid
+            keypair: Keypair::from_bytes(&*Zeroizing::new(self.keypair.to_bytes())).unwrap(),
+        }
+    }
+}
+
+impl fmt::Debug for DeviceKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DeviceKey")
+            .field("id", &self.id)
+            .field("public_key", &self.keypair.public)
+            .finish()
+    }
+}
+
+///
+/// Signer of a payload
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Signer {
+    /// Key identifier
+    pub key_id: String,
+    /// Public key
+    pub public_key: [u8; PUBLIC_KEY_LENGTH],
+}
+
+///
+/// Public keys of the trusted device keys, by key id
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrustedKeys {
+    keys: HashMap<String, [u8; PUBLIC_KEY_LENGTH]>,
+}
+
+impl TrustedKeys {
+    ///
+    /// Create Instance
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Trust the public key of a key id, replacing the public key with the same id
+    ///
+    pub fn trust<I: Into<String>>(
+        &mut self,
+        key_id: I,
+        public_key: [u8; PUBLIC_KEY_LENGTH],
+    ) -> &mut Self {
+        self.keys.insert(key_id.into(), public_key);
+        self
+    }
+
+    ///
+    /// Trusted public key of a key id
+    ///
+    pub fn get(&self, key_id: &str) -> Option<&[u8; PUBLIC_KEY_LENGTH]> {
+        self.keys.get(key_id)
+    }
+
+    ///
+    /// Stop trusting a key id
+    ///
+    pub fn remove(&mut self, key_id: &str) -> Option<[u8; PUBLIC_KEY_LENGTH]> {
+        self.keys.remove(key_id)
+    }
+}
+
+///
+/// Check if the data is signed
+///
+pub fn is_signed(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+///
+/// Sign the data with the device key
+///
+pub fn sign(key: &DeviceKey, data: &[u8]) -> anyhow::Result<Vec<u8>> {
+    if key.id.len() > u8::MAX as usize {
+        return Err(anyhow::anyhow!(
+            "Device key ids are limited to {} bytes",
+            u8::MAX
+        ));
+    }
+    let mut header = Vec::with_capacity(MAGIC.len() + 1 + key.id.len() + PUBLIC_KEY_LENGTH);
+    header.extend_from_slice(&MAGIC);
+    header.push(key.id.len() as u8);
+    header.extend_from_slice(key.id.as_bytes());
+    header.extend_from_slice(&key.public_key());
+
+    let signature = key.keypair.sign(&[&header[..], data].concat());
+
+    let mut out = header;
+    out.extend_from_slice(&signature.to_bytes());
+    out.extend_from_slice(data);
+    Ok(out)
+}
+
+///
+/// Verify the signature of the data with the trusted public key of its key id, returns the
+/// signer and the signed data
+///
+pub fn verify<'a>(
+    data: &'a [u8],
+    trusted: &TrustedKeys,
+) -> Result<(Signer, &'a [u8]), PayloadError> {
+    let truncated = || PayloadError::InvalidEncoding("Truncated signed data".to_string());
+    let id_len = *data.get(MAGIC.len()).ok_or_else(truncated)? as usize;
+    let header_len = MAGIC.len() + 1 + id_len + PUBLIC_KEY_LENGTH;
+    if !is_signed(data) || data.len() < header_len + SIGNATURE_LENGTH {
+        return Err(truncated());
+    }
+    let (header, rest) = data.split_at(header_len);
+    let (signature, signed) = rest.split_at(SIGNATURE_LENGTH);
+    let key_id = String::from_utf8_lossy(&header[MAGIC.len() + 1..header_len - PUBLIC_KEY_LENGTH])
+        .to_string();
+
+    let public_key = match trusted.get(&key_id) {
+        Some(public_key) if public_key[..] == header[header_len - PUBLIC_KEY_LENGTH..] => {
+            public_key
+        }
+        _ => return Err(PayloadError::UntrustedSigner(key_id)),
+    };
+
+    let invalid = || PayloadError::InvalidSignature(key_id.clone());
+    let public_key = PublicKey::from_bytes(public_key).map_err(|_| invalid())?;
+    let signature = Signature::try_from(signature).map_err(|_| invalid())?;
+    public_key
+        .verify(&[header, signed].concat(), &signature)
+        .map_err(|_| invalid())?;
+
+    Ok((
+        Signer {
+            key_id: key_id.clone(),
+            public_key: public_key.to_bytes(),
+        },
+        signed,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_signed, verify, DeviceKey, TrustedKeys};
+    use crate::{
+        payload::{json, PacketPayload, PayloadError},
+        sample::StreamsData,
+    };
+
+    #[test]
+    fn sign_and_verify() {
+        let key = DeviceKey::generate("sensor-01");
+        let data = StreamsData::default();
+        let payload = json::PayloadBuilder::new()
+            .public(&data)
+            .unwrap()
+            .sign(&key)
+            .unwrap()
+            .build();
+        assert!(is_signed(&payload.public_data().0));
+
+        let mut trusted = TrustedKeys::new();
+        trusted.trust("sensor-01", key.public_key());
+        let (signer, _) = verify(&payload.public_data().0, &trusted).unwrap();
+        assert_eq!(signer.key_id, "sensor-01");
+        assert_eq!(signer.public_key, key.public_key());
+
+        let public: StreamsData =
+            json::Payload::unwrap_data_with(payload.public_data(), &Default::default(), &trusted)
+                .unwrap();
+        assert_eq!(public, data);
+    }
+
+    #[test]
+    fn reject_untrusted_signer() {
+        let key = DeviceKey::generate("sensor-01");
+        let payload = json::PayloadBuilder::new()
+            .public(&StreamsData::default())
+            .unwrap()
+            .sign(&key)
+            .unwrap()
+            .build();
+
+        // A key id which is not trusted
+        assert!(matches!(
+            json::Payload::unwrap_data::<StreamsData>(payload.public_data()),
+            Err(PayloadError::UntrustedSigner(key_id)) if key_id == "sensor-01"
+        ));
+
+        // A forged key with the key id of a trusted key
+        let mut trusted = TrustedKeys::new();
+        trusted.trust("sensor-01", DeviceKey::generate("sensor-01").public_key());
+        assert!(matches!(
+            verify(&payload.public_data().0, &trusted),
+            Err(PayloadError::UntrustedSigner(_))
+        ));
+        assert!(matches!(
+            json::Payload::signer(payload.public_data(), &trusted),
+            Err(PayloadError::UntrustedSigner(_))
+        ));
+
+        trusted.trust("sensor-01", key.public_key());
+        let signer = json::Payload::signer(payload.public_data(), &trusted).unwrap();
+        assert_eq!(signer.unwrap().public_key, key.public_key());
+    }
+
+    #[test]
+    fn reject_tampered_data() {
+        let key = DeviceKey::from_hex("sensor-02", &DeviceKey::generate("x").to_hex()).unwrap();
+        let payload = json::PayloadBuilder::new()
+            .masked(&StreamsData::default())
+            .unwrap()
+            .sign(&key)
+            .unwrap()
+            .build();
+
+        let mut tampered = payload.masked_data().clone();
+        let last = tampered.0.len() - 2;
+        tampered.0[last] ^= 0x01;
+        let mut trusted = TrustedKeys::new();
+        trusted.trust("sensor-02", key.public_key());
+        match json::Payload::unwrap_data_with::<StreamsData>(
+            &tampered,
+            &Default::default(),
+            &trusted,
+        ) {
+            Err(PayloadError::InvalidSignature(key_id)) => assert_eq!(key_id, "sensor-02"),
+            other => panic!("Unexpected result {:?}", other),
+        }
+    }
+}
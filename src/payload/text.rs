@@ -0,0 +1,85 @@
+//!
+//! Text Encoding module
+//!
+//! Payloads encoded as URL-safe base64 text, for gateways which only carry printable ASCII.
+//! The `~` prefix is not part of the base64 alphabet, so text payloads are told apart from the
+//! binary ones.
+//!
+//! | prefix | URL-safe base64 of the payload |
+//! |--------|--------------------------------|
+//! | `~`    | ...                            |
+//!
+use super::PayloadError;
+use base64::URL_SAFE;
+
+///
+/// Prefix of the text payloads
+///
+pub const PREFIX: u8 = b'~';
+
+///
+/// Check if the data is a text payload
+///
+pub fn is_text(data: &[u8]) -> bool {
+    data.first() == Some(&PREFIX)
+}
+
+///
+/// Encode the data as text
+///
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![PREFIX];
+    out.extend_from_slice(base64::encode_config(data, URL_SAFE).as_bytes());
+    out
+}
+
+///
+/// Decode the text payload
+///
+pub fn decode(data: &[u8]) -> Result<Vec<u8>, PayloadError> {
+    if !is_text(data) {
+        return Err(PayloadError::InvalidEncoding(
+            "Missing text payload prefix".to_string(),
+        ));
+    }
+    base64::decode_config(&data[1..], URL_SAFE)
+        .map_err(|e| PayloadError::InvalidEncoding(format!("Text payload: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, is_text};
+    use crate::{
+        payload::{json, PacketPayload, PayloadError},
+        sample::StreamsData,
+    };
+
+    #[test]
+    fn text_safe_payload() {
+        let data = StreamsData::new("Sala de calderas ñ", 21.5, 101_325.0);
+        let payload = json::PayloadBuilder::new()
+            .public(&data)
+            .unwrap()
+            .text_safe()
+            .unwrap()
+            .build();
+        assert!(is_text(&payload.public_data().0));
+        assert!(payload.public_data().0.iter().all(|c| c.is_ascii_graphic()));
+
+        let public: StreamsData = json::Payload::unwrap_data(payload.public_data()).unwrap();
+        assert_eq!(public, data);
+        assert!(payload.masked_data().0.is_empty());
+    }
+
+    #[test]
+    fn reject_invalid_text() {
+        assert_eq!(
+            decode(&encode(&[0xE1, 0x01, 0xFF])).unwrap(),
+            vec![0xE1, 0x01, 0xFF]
+        );
+        assert!(matches!(
+            decode(b"~not base64!"),
+            Err(PayloadError::InvalidEncoding(_))
+        ));
+    }
+}
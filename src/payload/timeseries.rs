@@ -0,0 +1,460 @@
+//!
+//! Time Series module
+//!
+//! Compact encoding for batches of numeric records. Timestamps are written as zigzag varint
+//! deltas of deltas, labels repeated by consecutive records are written once and every value
+//! column is compressed with the XOR scheme of Facebook's Gorilla. Decoding rebuilds the exact
+//! values.
+//!
+//! | count  | values per record | timestamps      | labels          | value columns |
+//! |--------|-------------------|-----------------|-----------------|---------------|
+//! | varint | varint            | varint per item | varint + utf8   | bit stream    |
+//!
+//! [`Series`] serializes the encoded records as bytes, or as a hex string with human readable
+//! serializers like JSON, so it can be sent with any [`PayloadSerializer`](super::PayloadSerializer).
+//!
+use super::PayloadSchema;
+use serde::{
+    de::{self, Deserializer, Visitor},
+    ser::{self, Serializer},
+    Deserialize, Serialize,
+};
+use std::{borrow::Cow, fmt, marker::PhantomData};
+
+///
+/// Record of a time series
+///
+pub trait SeriesRecord: Sized {
+    ///
+    /// Number of values of each record
+    ///
+    const VALUES: usize;
+
+    ///
+    /// Record timestamp, any integer unit like milliseconds or nanoseconds since the epoch
+    ///
+    /// Fails when the timestamp does not fit in the unit
+    ///
+    fn timestamp(&self) -> anyhow::Result<i64>;
+
+    ///
+    /// Record label, Default: empty
+    ///
+    fn label(&self) -> &str {
+        ""
+    }
+
+    ///
+    /// Record values, exactly `VALUES` of them
+    ///
+    fn values(&self) -> Vec<f64>;
+
+    ///
+    /// Rebuild the record from its timestamp, label and values
+    ///
+    fn from_series(timestamp: i64, label: String, values: &[f64]) -> Self;
+}
+
+///
+/// Records encoded as a time series
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series<R> {
+    records: Vec<R>,
+}
+
+impl<R> Series<R> {
+    ///
+    /// Create Instance
+    ///
+    pub fn new(records: Vec<R>) -> Self {
+        Series { records }
+    }
+
+    ///
+    /// Records of the series
+    ///
+    pub fn records(&self) -> &[R] {
+        &self.records
+    }
+
+    ///
+    /// Take the records of the series
+    ///
+    pub fn into_records(self) -> Vec<R> {
+        self.records
+    }
+}
+
+impl<R> From<Vec<R>> for Series<R> {
+    fn from(records: Vec<R>) -> Self {
+        Series::new(records)
+    }
+}
+
+impl<R: PayloadSchema> PayloadSchema for Series<R> {
+    fn schema_type() -> Cow<'static, str> {
+        Cow::Owned(format!("{}.series", R::schema_type()))
+    }
+
+    fn schema_version() -> u16 {
+        R::schema_version()
+    }
+}
+
+impl<R: SeriesRecord> Serialize for Series<R> {
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        let encoded = encode(&self.records).map_err(ser::Error::custom)?;
+        if serializer.is_human_readable() {
+            let mut out = vec![0u8; encoded.len() * 2];
+            hex::encode_to_slice(&encoded, &mut out).map_err(ser::Error::custom)?;
+            serializer.serialize_str(std::str::from_utf8(&out).map_err(ser::Error::custom)?)
+        } else {
+            serializer.serialize_bytes(&encoded)
+        }
+    }
+}
+
+impl<'de, R: SeriesRecord> Deserialize<'de> for Series<R> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_str(SeriesVisitor(PhantomData))
+        } else {
+            deserializer.deserialize_bytes(SeriesVisitor(PhantomData))
+        }
+    }
+}
+
+struct SeriesVisitor<R>(PhantomData<R>);
+
+impl<'de, R: SeriesRecord> Visitor<'de> for SeriesVisitor<R> {
+    type Value = Series<R>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("time series bytes or hex string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        let mut raw = vec![0u8; v.len() / 2];
+        hex::decode_to_slice(v, &mut raw).map_err(E::custom)?;
+        self.visit_bytes(&raw)
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+        decode(v).map(Series::new).map_err(E::custom)
+    }
+}
+
+///
+/// Encode the records
+///
+pub fn encode<R: SeriesRecord>(records: &[R]) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write_varint(&mut out, records.len() as u64);
+    write_varint(&mut out, R::VALUES as u64);
+
+    let mut prev = (0i64, 0i64);
+    for (idx, record) in records.iter().enumerate() {
+        let timestamp = record.timestamp()?;
+        let delta = timestamp.wrapping_sub(prev.0);
+        let dod = if idx < 2 {
+            delta
+        } else {
+            delta.wrapping_sub(prev.1)
+        };
+        write_varint(&mut out, zigzag(dod));
+        prev = (timestamp, delta);
+    }
+
+    let mut last_label = "";
+    for (idx, record) in records.iter().enumerate() {
+        let label = record.label();
+        if idx > 0 && label == last_label {
+            write_varint(&mut out, 0);
+        } else {
+            write_varint(&mut out, label.len() as u64 + 1);
+            out.extend_from_slice(label.as_bytes());
+        }
+        last_label = label;
+    }
+
+    let mut columns = vec![Vec::with_capacity(records.len()); R::VALUES];
+    for record in records {
+        let values = record.values();
+        if values.len() != R::VALUES {
+            return Err(anyhow::anyhow!(
+                "Time series record with {} values, expected {}",
+                values.len(),
+                R::VALUES
+            ));
+        }
+        for (column, value) in columns.iter_mut().zip(values) {
+            column.push(value);
+        }
+    }
+    let mut bits = BitWriter::default();
+    for column in columns {
+        bits.xor_column(&column);
+    }
+    out.extend_from_slice(&bits.bytes);
+    Ok(out)
+}
+
+///
+/// Decode the records
+///
+pub fn decode<R: SeriesRecord>(data: &[u8]) -> anyhow::Result<Vec<R>> {
+    let mut cursor = data;
+    let count = read_varint(&mut cursor)? as usize;
+    let values = read_varint(&mut cursor)? as usize;
+    if values != R::VALUES {
+        return Err(anyhow::anyhow!(
+            "Time series with {} values per record, expected {}",
+            values,
+            R::VALUES
+        ));
+    }
+    // Every record takes at least one byte for its timestamp and another one for its label
+    if count > cursor.len() / 2 {
+        return Err(anyhow::anyhow!("Truncated time series"));
+    }
+
+    let mut timestamps = Vec::with_capacity(count);
+    let mut prev = (0i64, 0i64);
+    for idx in 0..count {
+        let dod = unzigzag(read_varint(&mut cursor)?);
+        let delta = if idx < 2 {
+            dod
+        } else {
+            dod.wrapping_add(prev.1)
+        };
+        prev = (prev.0.wrapping_add(delta), delta);
+        timestamps.push(prev.0);
+    }
+
+    let mut labels = Vec::with_capacity(count);
+    let mut last_label = String::new();
+    for _ in 0..count {
+        match read_varint(&mut cursor)? as usize {
+            0 => {}
+            len => {
+                if cursor.len() < len - 1 {
+                    return Err(anyhow::anyhow!("Truncated time series"));
+                }
+                let (label, tail) = cursor.split_at(len - 1);
+                last_label =
+                    String::from_utf8(label.to_vec()).map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+                cursor = tail;
+            }
+        }
+        labels.push(last_label.clone());
+    }
+
+    let mut bits = BitReader::new(cursor);
+    let columns = (0..values)
+        .map(|_| bits.xor_column(count))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    Ok(timestamps
+        .into_iter()
+        .zip(labels)
+        .enumerate()
+        .map(|(idx, (timestamp, label))| {
+            let values: Vec<f64> = columns.iter().map(|column| column[idx]).collect();
+            R::from_series(timestamp, label, &values)
+        })
+        .collect())
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(cursor: &mut &[u8]) -> anyhow::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (byte, tail) = cursor
+            .split_first()
+            .ok_or_else(|| anyhow::anyhow!("Truncated time series"))?;
+        *cursor = tail;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(anyhow::anyhow!("Invalid time series varint"))
+}
+
+///
+/// Bit stream of the value columns, most significant bit first
+///
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    used: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, bits: u32) {
+        for bit in (0..bits).rev() {
+            let offset = self.used % 8;
+            if offset == 0 {
+                self.bytes.push(0);
+            }
+            if (value >> bit) & 1 == 1 {
+                *self.bytes.last_mut().unwrap() |= 0x80 >> offset;
+            }
+            self.used += 1;
+        }
+    }
+
+    ///
+    /// Write the first value as is, the next ones XOR'ed with the previous value:
+    ///
+    /// * `0`: same value
+    /// * `10` + meaningful bits: inside the leading and trailing zeros of the previous block
+    /// * `11` + 5 bits leading zeros + 6 bits length - 1 + meaningful bits
+    ///
+    fn xor_column(&mut self, column: &[f64]) {
+        let mut prev = match column.first() {
+            Some(first) => first.to_bits(),
+            None => return,
+        };
+        self.write(prev, 64);
+
+        let mut block: Option<(u32, u32)> = None;
+        for value in &column[1..] {
+            let xor = prev ^ value.to_bits();
+            prev = value.to_bits();
+            if xor == 0 {
+                self.write(0, 1);
+                continue;
+            }
+            let leading = xor.leading_zeros().min(31);
+            let trailing = xor.trailing_zeros();
+            match block {
+                Some((b_leading, b_trailing)) if leading >= b_leading && trailing >= b_trailing => {
+                    self.write(0b10, 2);
+                    self.write(xor >> b_trailing, 64 - b_leading - b_trailing);
+                }
+                _ => {
+                    let len = 64 - leading - trailing;
+                    self.write(0b11, 2);
+                    self.write(leading as u64, 5);
+                    self.write(len as u64 - 1, 6);
+                    self.write(xor >> trailing, len);
+                    block = Some((leading, trailing));
+                }
+            }
+        }
+    }
+}
+
+struct BitReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        BitReader { bytes, pos: 0 }
+    }
+
+    fn read(&mut self, bits: u32) -> anyhow::Result<u64> {
+        let mut value = 0u64;
+        for _ in 0..bits {
+            let byte = self
+                .bytes
+                .get(self.pos / 8)
+                .ok_or_else(|| anyhow::anyhow!("Truncated time series"))?;
+            value = (value << 1) | ((byte >> (7 - self.pos % 8)) & 1) as u64;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    fn xor_column(&mut self, count: usize) -> anyhow::Result<Vec<f64>> {
+        let mut column = Vec::with_capacity(count);
+        if count == 0 {
+            return Ok(column);
+        }
+        let mut prev = self.read(64)?;
+        column.push(f64::from_bits(prev));
+
+        let mut block = (0u32, 0u32);
+        for _ in 1..count {
+            if self.read(1)? == 1 {
+                if self.read(1)? == 1 {
+                    let leading = self.read(5)? as u32;
+                    let len = self.read(6)? as u32 + 1;
+                    if leading + len > 64 {
+                        return Err(anyhow::anyhow!("Invalid time series block"));
+                    }
+                    block = (leading, 64 - leading - len);
+                }
+                let len = 64 - block.0 - block.1;
+                prev ^= self.read(len)? << block.1;
+            }
+            column.push(f64::from_bits(prev));
+        }
+        Ok(column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decode, encode, Series};
+    use crate::{
+        payload::{
+            json::{self, JsonSerializer},
+            PacketPayload, PayloadSerializer,
+        },
+        sample::StreamsData,
+    };
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn encode_exact_values() {
+        let readings: Vec<StreamsData> = (0..64).map(|_| StreamsData::default()).collect();
+        let encoded = encode(&readings).unwrap();
+        assert_eq!(decode::<StreamsData>(&encoded).unwrap(), readings);
+        assert!(decode::<StreamsData>(&encoded[..encoded.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn reject_out_of_range_timestamp() {
+        let far = Utc.with_ymd_and_hms(2300, 1, 1, 0, 0, 0).unwrap();
+        let readings = vec![StreamsData::new_at(far, "boiler", 21.5, 1013.0)];
+        assert!(encode(&readings).is_err());
+        assert!(JsonSerializer::serialize_data(&Series::new(readings)).is_err());
+    }
+
+    #[test]
+    fn series_payload_is_smaller() {
+        let readings: Vec<StreamsData> = (0..64)
+            .map(|idx| StreamsData::new("boiler", 21.5 + (idx / 8) as f32 * 0.25, 1013.0))
+            .collect();
+        let series = Series::new(readings.clone());
+
+        let plain = JsonSerializer::serialize_data(&readings).unwrap();
+        let packed = JsonSerializer::serialize_data(&series).unwrap();
+        assert!(packed.len() * 3 < plain.len());
+
+        let payload = json::PayloadBuilder::new().masked(&series).unwrap().build();
+        let unwrapped: Series<StreamsData> =
+            json::Payload::unwrap_data(payload.masked_data()).unwrap();
+        assert_eq!(unwrapped.into_records(), readings);
+    }
+}
@@ -0,0 +1,257 @@
+//!
+//! JSON Schema Validation module
+//!
+//! JSON payloads validated against a [JSON Schema](https://json-schema.org) before they are
+//! decoded. Schemas are attached per message type, by the type id of the envelope, or per
+//! channel, by the channel address, and every schema which applies to the payload is checked.
+//!
+//! ```ignore
+//! let mut schemas = SchemaSet::new();
+//! schemas
+//!     .insert_type("streams-data", JsonSchema::load("streams-data.schema.json")?)
+//!     .insert_channel(channel_address, JsonSchema::compile(&channel_schema)?);
+//!
+//! registry.validate_with(schemas);
+//! ```
+//!
+use super::{cloudevents, compress, envelope::Envelope, PayloadError};
+use jsonschema::JSONSchema;
+use serde_json::Value;
+use std::{borrow::Cow, collections::HashMap, fmt, fs, path::Path};
+
+///
+/// Compiled JSON Schema
+///
+#[derive(Debug)]
+pub struct JsonSchema(JSONSchema);
+
+impl JsonSchema {
+    ///
+    /// Compile the schema
+    ///
+    pub fn compile(schema: &Value) -> anyhow::Result<Self> {
+        JSONSchema::compile(schema)
+            .map(JsonSchema)
+            .map_err(|e| anyhow::anyhow!("Invalid JSON Schema: {}", e))
+    }
+
+    ///
+    /// Load and compile the schema file
+    ///
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let raw = fs::read(path)?;
+        let schema = serde_json::from_slice(&raw).map_err(|e| anyhow::anyhow!("{:#?}", e))?;
+        Self::compile(&schema)
+    }
+
+    ///
+    /// Validate the JSON value, returns the violations of the schema
+    ///
+    pub fn validate(&self, value: &Value) -> Vec<Violation> {
+        match self.0.validate(value) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors
+                .map(|e| Violation {
+                    path: e.instance_path.to_string(),
+                    message: e.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+///
+/// Value which does not match the schema
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    /// JSON pointer of the value, empty for the whole document
+    pub path: String,
+    /// Description of the violation
+    pub message: String,
+}
+
+///
+/// Result of the validation of a payload
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    /// Type identifier of the payload envelope
+    pub type_id: String,
+    /// Number of schemas checked
+    pub schemas: usize,
+    /// Violations of the schemas
+    pub violations: Vec<Violation>,
+}
+
+impl ValidationReport {
+    ///
+    /// Check if the payload matches every schema
+    ///
+    pub fn is_valid(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} checked against {} schemas, {} violations",
+            self.type_id,
+            self.schemas,
+            self.violations.len()
+        )?;
+        for violation in self.violations.iter() {
+            write!(f, "; {}: {}", violation.path, violation.message)?;
+        }
+        Ok(())
+    }
+}
+
+///
+/// JSON Schemas per message type and per channel
+///
+#[derive(Debug, Default)]
+pub struct SchemaSet {
+    types: HashMap<String, JsonSchema>,
+    channels: HashMap<String, JsonSchema>,
+}
+
+impl SchemaSet {
+    ///
+    /// Create Instance
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    ///
+    /// Attach the schema to the payloads with the type id, replacing the previous one
+    ///
+    pub fn insert_type<I: Into<String>>(&mut self, type_id: I, schema: JsonSchema) -> &mut Self {
+        self.types.insert(type_id.into(), schema);
+        self
+    }
+
+    ///
+    /// Attach the schema to the payloads of the channel address, replacing the previous one
+    ///
+    pub fn insert_channel<A: Into<String>>(&mut self, address: A, schema: JsonSchema) -> &mut Self {
+        self.channels.insert(address.into(), schema);
+        self
+    }
+
+    ///
+    /// Check if there are no schemas
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty() && self.channels.is_empty()
+    }
+
+    ///
+    /// Validate the JSON data described by the envelope against the schemas of its type and
+    /// of the channel, returns `None` when no schema applies or the data is not JSON
+    ///
+    pub fn validate(
+        &self,
+        channel: Option<&str>,
+        envelope: &Envelope,
+        data: &[u8],
+    ) -> Result<Option<ValidationReport>, PayloadError> {
+        let content_type = compress::serializer_content_type(&envelope.content_type)
+            .unwrap_or(&envelope.content_type);
+        if !cloudevents::is_json(content_type) {
+            return Ok(None);
+        }
+        let schemas: Vec<&JsonSchema> = self
+            .types
+            .get(&envelope.type_id)
+            .into_iter()
+            .chain(channel.and_then(|address| self.channels.get(address)))
+            .collect();
+        if schemas.is_empty() {
+            return Ok(None);
+        }
+
+        let deserialize = |source| PayloadError::Deserialize {
+            bytes: data.to_vec(),
+            source,
+        };
+        let json = if content_type == envelope.content_type {
+            Cow::Borrowed(data)
+        } else {
+            compress::unpack(data).map_err(deserialize)?
+        };
+        let value: Value =
+            serde_json::from_slice(&json).map_err(|e| deserialize(anyhow::anyhow!("{:#?}", e)))?;
+        Ok(Some(ValidationReport {
+            type_id: envelope.type_id.clone(),
+            schemas: schemas.len(),
+            violations: schemas.iter().flat_map(|s| s.validate(&value)).collect(),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JsonSchema, SchemaSet};
+    use crate::{
+        payload::{envelope::Envelope, json::JsonSerializer},
+        sample::StreamsData,
+    };
+    use serde_json::json;
+
+    fn temperature_schema(maximum: f64) -> JsonSchema {
+        JsonSchema::compile(&json!({
+            "type": "object",
+            "properties": { "temperature": { "type": "number", "maximum": maximum } },
+            "required": ["temperature"]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn validate_type_and_channel_schemas() {
+        let mut schemas = SchemaSet::new();
+        schemas
+            .insert_type("streams-data", temperature_schema(100.0))
+            .insert_channel("channel-a", temperature_schema(50.0));
+        let envelope = Envelope::new::<StreamsData, JsonSerializer>();
+
+        let data = br#"{"temperature": 75.0}"#;
+        let report = schemas.validate(None, &envelope, data).unwrap().unwrap();
+        assert!(report.is_valid());
+        assert_eq!(report.schemas, 1);
+
+        let report = schemas
+            .validate(Some("channel-a"), &envelope, data)
+            .unwrap()
+            .unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(report.schemas, 2);
+        assert_eq!(report.violations[0].path, "/temperature");
+
+        assert!(schemas
+            .validate(Some("channel-b"), &envelope, br#"{}"#)
+            .unwrap()
+            .map_or(false, |report| !report.is_valid()));
+    }
+
+    #[test]
+    fn skip_other_payloads() {
+        let mut schemas = SchemaSet::new();
+        schemas.insert_type("status", temperature_schema(100.0));
+
+        let mut envelope = Envelope::new::<StreamsData, JsonSerializer>();
+        assert!(schemas.validate(None, &envelope, b"{}").unwrap().is_none());
+
+        envelope.type_id = "status".to_string();
+        envelope.content_type = "application/cbor".to_string();
+        assert!(schemas
+            .validate(None, &envelope, &[0xA0])
+            .unwrap()
+            .is_none());
+        assert!(JsonSchema::compile(&json!({ "type": 12 })).is_err());
+    }
+}
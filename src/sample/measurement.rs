@@ -0,0 +1,439 @@
+//!
+//! Measurement Model
+//!
+//! Readings of many sensors of a device in one record, each with its quantity, unit, value and
+//! uncertainty. Values convert between the units of the same quantity through the SI unit.
+//!
+//! | quantity    | SI unit | other units                |
+//! |-------------|---------|----------------------------|
+//! | temperature | `K`     | `Cel`, `degF`              |
+//! | pressure    | `Pa`    | `hPa`, `kPa`, `bar`, `psi` |
+//! | humidity    | `%RH`   |                            |
+//! | co2         | `ppm`   | `ppb`                      |
+//! | vibration   | `m/s2`  | `g`                        |
+//! | latitude    | `lat`   |                            |
+//! | longitude   | `lon`   |                            |
+//! | altitude    | `m`     | `ft`                       |
+//!
+//! [`StreamsData`] converts to and from a measurement with the `temperature` and `pressure`
+//! sensors.
+//!
+use super::StreamsData;
+use crate::clock::Timestamped;
+use crate::payload::PayloadSchema;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::{borrow::Cow, convert::TryFrom};
+
+///
+/// Physical quantity of a reading
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Quantity {
+    /// Temperature
+    Temperature,
+    /// Pressure
+    Pressure,
+    /// Relative humidity
+    Humidity,
+    /// CO2 concentration
+    Co2,
+    /// Vibration acceleration
+    Vibration,
+    /// GPS latitude
+    Latitude,
+    /// GPS longitude
+    Longitude,
+    /// GPS altitude
+    Altitude,
+}
+
+impl Quantity {
+    ///
+    /// SI unit of the quantity
+    ///
+    pub fn si_unit(&self) -> Unit {
+        match self {
+            Quantity::Temperature => Unit::Kelvin,
+            Quantity::Pressure => Unit::Pascal,
+            Quantity::Humidity => Unit::RelativeHumidity,
+            Quantity::Co2 => Unit::PartsPerMillion,
+            Quantity::Vibration => Unit::MetrePerSecondSquared,
+            Quantity::Latitude => Unit::Latitude,
+            Quantity::Longitude => Unit::Longitude,
+            Quantity::Altitude => Unit::Metre,
+        }
+    }
+}
+
+///
+/// Unit of a reading, written with its SenML symbol
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Unit {
+    /// Kelvin
+    #[serde(rename = "K")]
+    Kelvin,
+    /// Degrees Celsius
+    #[serde(rename = "Cel")]
+    Celsius,
+    /// Degrees Fahrenheit
+    #[serde(rename = "degF")]
+    Fahrenheit,
+    /// Pascal
+    #[serde(rename = "Pa")]
+    Pascal,
+    /// Hectopascal
+    #[serde(rename = "hPa")]
+    Hectopascal,
+    /// Kilopascal
+    #[serde(rename = "kPa")]
+    Kilopascal,
+    /// Bar
+    #[serde(rename = "bar")]
+    Bar,
+    /// Pound per square inch
+    #[serde(rename = "psi")]
+    Psi,
+    /// Percentage of relative humidity
+    #[serde(rename = "%RH")]
+    RelativeHumidity,
+    /// Parts per million
+    #[serde(rename = "ppm")]
+    PartsPerMillion,
+    /// Parts per billion
+    #[serde(rename = "ppb")]
+    PartsPerBillion,
+    /// Metre per second squared
+    #[serde(rename = "m/s2")]
+    MetrePerSecondSquared,
+    /// Standard gravity
+    #[serde(rename = "g")]
+    StandardGravity,
+    /// Degrees north of the equator
+    #[serde(rename = "lat")]
+    Latitude,
+    /// Degrees east of Greenwich
+    #[serde(rename = "lon")]
+    Longitude,
+    /// Metre
+    #[serde(rename = "m")]
+    Metre,
+    /// Foot
+    #[serde(rename = "ft")]
+    Foot,
+}
+
+impl Unit {
+    ///
+    /// SI unit of the same quantity
+    ///
+    pub fn si(&self) -> Unit {
+        match self {
+            Unit::Kelvin | Unit::Celsius | Unit::Fahrenheit => Unit::Kelvin,
+            Unit::Pascal | Unit::Hectopascal | Unit::Kilopascal | Unit::Bar | Unit::Psi => {
+                Unit::Pascal
+            }
+            Unit::PartsPerMillion | Unit::PartsPerBillion => Unit::PartsPerMillion,
+            Unit::MetrePerSecondSquared | Unit::StandardGravity => Unit::MetrePerSecondSquared,
+            Unit::Metre | Unit::Foot => Unit::Metre,
+            Unit::RelativeHumidity | Unit::Latitude | Unit::Longitude => *self,
+        }
+    }
+
+    ///
+    /// Scale and offset of the unit, `si = value * scale + offset`
+    ///
+    fn scale(&self) -> (f64, f64) {
+        match self {
+            Unit::Celsius => (1.0, 273.15),
+            Unit::Fahrenheit => (5.0 / 9.0, 459.67 * 5.0 / 9.0),
+            Unit::Hectopascal => (1e2, 0.0),
+            Unit::Kilopascal => (1e3, 0.0),
+            Unit::Bar => (1e5, 0.0),
+            Unit::Psi => (6_894.757_293_168, 0.0),
+            Unit::PartsPerBillion => (1e-3, 0.0),
+            Unit::StandardGravity => (9.806_65, 0.0),
+            Unit::Foot => (0.3048, 0.0),
+            _ => (1.0, 0.0),
+        }
+    }
+
+    ///
+    /// Convert the value in this unit to the SI unit
+    ///
+    pub fn to_si(&self, value: f64) -> f64 {
+        let (scale, offset) = self.scale();
+        value * scale + offset
+    }
+
+    ///
+    /// Convert the value in the SI unit to this unit
+    ///
+    pub fn from_si(&self, value: f64) -> f64 {
+        let (scale, offset) = self.scale();
+        (value - offset) / scale
+    }
+
+    ///
+    /// Convert the value in this unit to another unit of the same quantity
+    ///
+    pub fn convert(&self, value: f64, to: Unit) -> anyhow::Result<f64> {
+        if self.si() != to.si() {
+            return Err(anyhow::anyhow!("Can not convert {:?} to {:?}", self, to));
+        }
+        Ok(to.from_si(self.to_si(value)))
+    }
+}
+
+///
+/// Reading of one sensor
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reading {
+    /// Sensor identifier
+    pub sensor_id: String,
+    /// Measured quantity
+    pub quantity: Quantity,
+    /// Unit of the value and the uncertainty
+    pub unit: Unit,
+    /// Measured value
+    pub value: f64,
+    /// Standard uncertainty of the value
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uncertainty: Option<f64>,
+}
+
+impl Reading {
+    ///
+    /// Create Instance, the unit must be a unit of the quantity
+    ///
+    pub fn new<I: Into<String>>(
+        sensor_id: I,
+        quantity: Quantity,
+        unit: Unit,
+        value: f64,
+    ) -> anyhow::Result<Self> {
+        if unit.si() != quantity.si_unit() {
+            return Err(anyhow::anyhow!(
+                "{:?} is not a unit of {:?}",
+                unit,
+                quantity
+            ));
+        }
+        Ok(Reading {
+            sensor_id: sensor_id.into(),
+            quantity,
+            unit,
+            value,
+            uncertainty: None,
+        })
+    }
+
+    ///
+    /// Standard uncertainty of the value, in the unit of the value
+    ///
+    pub fn with_uncertainty(mut self, uncertainty: f64) -> Self {
+        self.uncertainty = Some(uncertainty);
+        self
+    }
+
+    ///
+    /// Reading converted to another unit of the quantity
+    ///
+    pub fn to_unit(&self, unit: Unit) -> anyhow::Result<Self> {
+        let value = self.unit.convert(self.value, unit)?;
+        // The offset of the units does not apply to uncertainties
+        let uncertainty = self
+            .uncertainty
+            .map(|u| u * self.unit.scale().0 / unit.scale().0);
+        Ok(Reading {
+            unit,
+            value,
+            uncertainty,
+            ..self.clone()
+        })
+    }
+
+    ///
+    /// Reading converted to the SI unit of the quantity
+    ///
+    pub fn to_si(&self) -> anyhow::Result<Self> {
+        self.to_unit(self.quantity.si_unit())
+    }
+}
+
+///
+/// Readings of the sensors of a device taken at the same time
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Measurement {
+    /// Timestamp of the readings, UTC with nanoseconds
+    #[serde(with = "crate::clock::rfc3339")]
+    pub ts: DateTime<Utc>,
+    /// Description of the measurement
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub desc: String,
+    /// Sensor readings
+    pub readings: Vec<Reading>,
+}
+
+impl Measurement {
+    ///
+    /// Create Instance without readings
+    ///
+    pub fn new<D: Into<String>>(ts: DateTime<Utc>, desc: D) -> Self {
+        Measurement {
+            ts,
+            desc: desc.into(),
+            readings: Vec::new(),
+        }
+    }
+
+    ///
+    /// Add a reading
+    ///
+    pub fn push(&mut self, reading: Reading) -> &mut Self {
+        self.readings.push(reading);
+        self
+    }
+
+    ///
+    /// Find the reading of the sensor
+    ///
+    pub fn sensor(&self, sensor_id: &str) -> Option<&Reading> {
+        self.readings.iter().find(|r| r.sensor_id == sensor_id)
+    }
+
+    ///
+    /// Readings of the quantity
+    ///
+    pub fn readings_of(&self, quantity: Quantity) -> impl Iterator<Item = &Reading> {
+        self.readings.iter().filter(move |r| r.quantity == quantity)
+    }
+}
+
+impl Timestamped for Measurement {
+    fn time(&self) -> DateTime<Utc> {
+        self.ts
+    }
+}
+
+impl PayloadSchema for Measurement {
+    fn schema_type() -> Cow<'static, str> {
+        Cow::Borrowed("measurement")
+    }
+}
+
+impl From<&StreamsData> for Measurement {
+    fn from(data: &StreamsData) -> Self {
+        Measurement {
+            ts: data.ts,
+            desc: data.desc.clone(),
+            readings: vec![
+                Reading {
+                    sensor_id: "temperature".to_string(),
+                    quantity: Quantity::Temperature,
+                    unit: Unit::Celsius,
+                    value: data.temperature as f64,
+                    uncertainty: None,
+                },
+                Reading {
+                    sensor_id: "pressure".to_string(),
+                    quantity: Quantity::Pressure,
+                    unit: Unit::Pascal,
+                    value: data.pressure as f64,
+                    uncertainty: None,
+                },
+            ],
+        }
+    }
+}
+
+impl TryFrom<&Measurement> for StreamsData {
+    type Error = anyhow::Error;
+
+    fn try_from(measurement: &Measurement) -> anyhow::Result<Self> {
+        let value = |quantity: Quantity, unit: Unit| -> anyhow::Result<f32> {
+            let reading = measurement
+                .readings_of(quantity)
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("Missing {:?} reading", quantity))?;
+            Ok(reading.unit.convert(reading.value, unit)? as f32)
+        };
+        Ok(StreamsData::new_at(
+            measurement.ts,
+            measurement.desc.clone(),
+            value(Quantity::Temperature, Unit::Celsius)?,
+            value(Quantity::Pressure, Unit::Pascal)?,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Measurement, Quantity, Reading, Unit};
+    use crate::{
+        payload::{json, PacketPayload},
+        sample::StreamsData,
+    };
+    use std::convert::TryFrom;
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!((value - expected).abs() < 1e-9, "{} != {}", value, expected);
+    }
+
+    #[test]
+    fn convert_units() {
+        assert_close(Unit::Celsius.convert(21.5, Unit::Kelvin).unwrap(), 294.65);
+        assert_close(
+            Unit::Fahrenheit.convert(212.0, Unit::Celsius).unwrap(),
+            100.0,
+        );
+        assert_close(
+            Unit::Bar.convert(1.013_25, Unit::Hectopascal).unwrap(),
+            1_013.25,
+        );
+        assert_close(
+            Unit::StandardGravity
+                .convert(2.0, Unit::MetrePerSecondSquared)
+                .unwrap(),
+            19.6133,
+        );
+        assert!(Unit::Celsius.convert(1.0, Unit::Pascal).is_err());
+
+        let reading = Reading::new("t1", Quantity::Temperature, Unit::Celsius, 20.0)
+            .unwrap()
+            .with_uncertainty(0.5)
+            .to_unit(Unit::Fahrenheit)
+            .unwrap();
+        assert_close(reading.value, 68.0);
+        assert_close(reading.uncertainty.unwrap(), 0.9);
+        assert!(Reading::new("h1", Quantity::Humidity, Unit::Kelvin, 1.0).is_err());
+    }
+
+    #[test]
+    fn migrate_streams_data() {
+        let data = StreamsData::new("Boiler room", 21.5, 101_325.0);
+        let mut measurement = Measurement::from(&data);
+        measurement.push(
+            Reading::new("co2-1", Quantity::Co2, Unit::PartsPerMillion, 415.0)
+                .unwrap()
+                .with_uncertainty(10.0),
+        );
+
+        let payload = json::PayloadBuilder::new()
+            .public(&measurement)
+            .unwrap()
+            .build();
+        let public: Measurement = json::Payload::unwrap_data(payload.public_data()).unwrap();
+        assert_eq!(public, measurement);
+        assert_eq!(public.sensor("co2-1").unwrap().uncertainty, Some(10.0));
+        assert_eq!(StreamsData::try_from(&public).unwrap(), data);
+
+        measurement.readings[0] = measurement.readings[0].to_si().unwrap();
+        assert_eq!(measurement.readings[0].unit, Unit::Kelvin);
+        assert_eq!(StreamsData::try_from(&measurement).unwrap(), data);
+    }
+}
@@ -0,0 +1,343 @@
+//!
+//! Measurement Sources
+//!
+//! Sensor readings consumed by the author loops, generated at random, replayed from recorded
+//! data or simulated.
+//!
+//! | source             | spec              |
+//! |--------------------|-------------------|
+//! | [`RandomSource`]   | `random[:<SEED>]` |
+//! | [`CsvSource`]      | `csv:<FILE>`      |
+//! | [`StdinSource`]    | `stdin`           |
+//! | [`SineWaveSource`] | `sine[:<PERIOD>]` |
+//!
+use super::StreamsData;
+use crate::clock::{Clock, SystemClock};
+use async_trait::async_trait;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::{f64::consts::PI, io::Cursor, path::Path, sync::Arc};
+use tokio::io::{stdin, AsyncBufReadExt, BufReader, Lines, Stdin};
+
+///
+/// Source of sensor readings
+///
+#[async_trait]
+pub trait MeasurementSource {
+    ///
+    /// Next reading, `None` when the source is exhausted
+    ///
+    async fn next_reading(&mut self) -> anyhow::Result<Option<StreamsData>>;
+}
+
+///
+/// Create the source described by the spec, see the table of the module
+///
+pub async fn open_source(spec: &str) -> anyhow::Result<Box<dyn MeasurementSource + Send>> {
+    let (kind, arg) = match spec.find(':') {
+        Some(pos) => (&spec[..pos], Some(&spec[pos + 1..])),
+        None => (spec, None),
+    };
+    match (kind, arg) {
+        ("random", None) => Ok(Box::new(RandomSource::new())),
+        ("random", Some(seed)) => {
+            let seed = seed
+                .parse()
+                .map_err(|e| anyhow::anyhow!("Invalid random seed {}: {:#?}", seed, e))?;
+            Ok(Box::new(RandomSource::seeded(seed)))
+        }
+        ("csv", Some(path)) => Ok(Box::new(CsvSource::open(path).await?)),
+        ("stdin", None) => Ok(Box::new(StdinSource::new())),
+        ("sine", period) => {
+            let period = match period {
+                Some(period) => period
+                    .parse()
+                    .map_err(|e| anyhow::anyhow!("Invalid sine period {}: {:#?}", period, e))?,
+                None => SineWaveSource::DEFAULT_PERIOD,
+            };
+            Ok(Box::new(SineWaveSource::new("sine-wave", period)))
+        }
+        _ => Err(anyhow::anyhow!("Unknown measurement source {}", spec)),
+    }
+}
+
+///
+/// Clock shared by the sources
+///
+type SharedClock = Arc<dyn Clock + Send + Sync>;
+
+///
+/// Random readings, never exhausted
+///
+pub struct RandomSource {
+    rng: ChaCha8Rng,
+    clock: SharedClock,
+}
+
+impl Default for RandomSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RandomSource {
+    ///
+    /// Create Instance
+    ///
+    pub fn new() -> Self {
+        RandomSource {
+            rng: ChaCha8Rng::from_entropy(),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    ///
+    /// Create Instance generating the same readings for the same seed
+    ///
+    /// The readings come from ChaCha8, unlike `StdRng` its stream does not change with the
+    /// platform. The values are sampled with `gen_range`, whose algorithm can change between rand
+    /// releases, so the readings are reproducible only with the same rand version
+    ///
+    pub fn seeded(seed: u64) -> Self {
+        RandomSource {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    ///
+    /// Clock of the reading timestamps, Default: `SystemClock`
+    ///
+    pub fn clock(&mut self, clock: SharedClock) -> &mut Self {
+        self.clock = clock;
+        self
+    }
+}
+
+#[async_trait]
+impl MeasurementSource for RandomSource {
+    async fn next_reading(&mut self) -> anyhow::Result<Option<StreamsData>> {
+        Ok(Some(StreamsData::random(&mut self.rng, &self.clock)))
+    }
+}
+
+///
+/// Readings replayed from a CSV file with the `ts,desc,temperature,pressure` header, the
+/// timestamps are written in RFC 3339 like `2021-04-01T18:19:49.123Z`, or without offset in UTC
+///
+pub struct CsvSource {
+    records: csv::DeserializeRecordsIntoIter<Cursor<Vec<u8>>, StreamsData>,
+}
+
+impl CsvSource {
+    ///
+    /// Open the CSV file
+    ///
+    pub async fn open<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let raw = tokio::fs::read(path).await?;
+        Ok(Self::from_bytes(raw))
+    }
+
+    ///
+    /// Read the CSV data
+    ///
+    pub fn from_bytes(raw: Vec<u8>) -> Self {
+        CsvSource {
+            records: csv::Reader::from_reader(Cursor::new(raw)).into_deserialize(),
+        }
+    }
+}
+
+#[async_trait]
+impl MeasurementSource for CsvSource {
+    async fn next_reading(&mut self) -> anyhow::Result<Option<StreamsData>> {
+        self.records
+            .next()
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("{:#?}", e))
+    }
+}
+
+///
+/// Readings read from the standard input as line-delimited JSON, empty lines are skipped
+///
+pub struct StdinSource {
+    lines: Lines<BufReader<Stdin>>,
+}
+
+impl Default for StdinSource {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StdinSource {
+    ///
+    /// Create Instance
+    ///
+    pub fn new() -> Self {
+        StdinSource {
+            lines: BufReader::new(stdin()).lines(),
+        }
+    }
+}
+
+#[async_trait]
+impl MeasurementSource for StdinSource {
+    async fn next_reading(&mut self) -> anyhow::Result<Option<StreamsData>> {
+        while let Some(line) = self.lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            return serde_json::from_str(&line)
+                .map(Some)
+                .map_err(|e| anyhow::anyhow!("{:#?}", e));
+        }
+        Ok(None)
+    }
+}
+
+///
+/// Simulated readings following a sine wave, never exhausted
+///
+#[derive(Clone)]
+pub struct SineWaveSource {
+    desc: String,
+    period: u32,
+    step: u32,
+    temperature: (f64, f64),
+    pressure: (f64, f64),
+    clock: SharedClock,
+}
+
+impl SineWaveSource {
+    ///
+    /// Readings of a whole wave, Default: 60
+    ///
+    pub const DEFAULT_PERIOD: u32 = 60;
+
+    ///
+    /// Create Instance, the wave repeats every `period` readings
+    ///
+    /// Default: temperature 20 ± 5, pressure 101325 ± 500
+    ///
+    pub fn new<D: Into<String>>(desc: D, period: u32) -> Self {
+        SineWaveSource {
+            desc: desc.into(),
+            period: period.max(1),
+            step: 0,
+            temperature: (20.0, 5.0),
+            pressure: (101_325.0, 500.0),
+            clock: Arc::new(SystemClock),
+        }
+    }
+
+    ///
+    /// Clock of the reading timestamps, Default: `SystemClock`
+    ///
+    pub fn clock(&mut self, clock: SharedClock) -> &mut Self {
+        self.clock = clock;
+        self
+    }
+
+    ///
+    /// Mean and amplitude of the temperature
+    ///
+    pub fn temperature(&mut self, mean: f64, amplitude: f64) -> &mut Self {
+        self.temperature = (mean, amplitude);
+        self
+    }
+
+    ///
+    /// Mean and amplitude of the pressure
+    ///
+    pub fn pressure(&mut self, mean: f64, amplitude: f64) -> &mut Self {
+        self.pressure = (mean, amplitude);
+        self
+    }
+
+    fn sample(&mut self) -> StreamsData {
+        let phase = (2.0 * PI * self.step as f64 / self.period as f64).sin();
+        self.step = (self.step + 1) % self.period;
+        StreamsData::new_at(
+            self.clock.now(),
+            self.desc.clone(),
+            (self.temperature.0 + self.temperature.1 * phase) as f32,
+            (self.pressure.0 + self.pressure.1 * phase) as f32,
+        )
+    }
+}
+
+#[async_trait]
+impl MeasurementSource for SineWaveSource {
+    async fn next_reading(&mut self) -> anyhow::Result<Option<StreamsData>> {
+        Ok(Some(self.sample()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{open_source, CsvSource, MeasurementSource, RandomSource, SineWaveSource};
+    use crate::{clock::FakeClock, sample::StreamsData};
+    use chrono::{Duration, TimeZone, Utc};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn replay_csv_file() {
+        let mut source = CsvSource::from_bytes(
+            b"ts,desc,temperature,pressure\n\
+              2021-04-01T18:19:49.123,Boiler room,21.5,101325\n\
+              2021-04-01T18:19:59,Boiler room,21.75,101300\n"
+                .to_vec(),
+        );
+
+        let first = source.next_reading().await.unwrap().unwrap();
+        assert_eq!(first.ts().to_string(), "2021-04-01 18:19:49.123 UTC");
+        let second = source.next_reading().await.unwrap().unwrap();
+        assert_eq!(serde_json::to_value(&second).unwrap()["temperature"], 21.75);
+        assert!(source.next_reading().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn sine_wave_readings() {
+        let mut source = SineWaveSource::new("sine", 4);
+        source.temperature(10.0, 2.0);
+
+        let mut temperatures = Vec::new();
+        for _ in 0..5 {
+            let reading: StreamsData = source.next_reading().await.unwrap().unwrap();
+            temperatures.push(serde_json::to_value(&reading).unwrap()["temperature"].as_f64());
+        }
+        let expected = [10.0, 12.0, 10.0, 8.0, 10.0];
+        for (temperature, expected) in temperatures.iter().zip(expected.iter()) {
+            assert!((temperature.unwrap() - expected).abs() < 1e-4);
+        }
+    }
+
+    #[tokio::test]
+    async fn seeded_random_readings() {
+        let start = Utc.timestamp_opt(1_617_300_000, 0).unwrap();
+        let readings = || async {
+            let mut source = RandomSource::seeded(7);
+            source.clock(Arc::new(FakeClock::new(start, Duration::seconds(10))));
+            let mut readings = Vec::new();
+            for _ in 0..3 {
+                readings.push(source.next_reading().await.unwrap().unwrap());
+            }
+            readings
+        };
+        let first = readings().await;
+        assert_eq!(first, readings().await);
+        assert_eq!(*first[2].ts(), start + Duration::seconds(20));
+    }
+
+    #[tokio::test]
+    async fn open_source_spec() {
+        assert!(open_source("random").await.is_ok());
+        assert!(open_source("random:42").await.is_ok());
+        assert!(open_source("sine:30").await.is_ok());
+        assert!(open_source("sine:x").await.is_err());
+        assert!(open_source("csv").await.is_err());
+        assert!(open_source("mqtt").await.is_err());
+    }
+}
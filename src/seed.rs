@@ -0,0 +1,184 @@
+//!
+//! Seed Module
+//!
+//! Channel seeds generated from the OS random number generator with 256 bits of entropy,
+//! written as 64 hex characters. A seed can be backed up as a 24 words
+//! [BIP39](https://github.com/bitcoin/bips/blob/master/bip-0039.mediawiki) mnemonic and restored
+//! from it.
+//!
+//! Seeds are held in [`Seed`], which wipes its memory on drop, never prints its value and is
+//! compared in constant time.
+//!
+use bip39::Mnemonic;
+use rand::{rngs::OsRng, Rng};
+use std::{fmt, str::FromStr};
+use subtle::ConstantTimeEq;
+use zeroize::{Zeroize, Zeroizing};
+
+///
+/// Bytes of entropy of a seed
+///
+pub const ENTROPY_BYTES: usize = 32;
+
+///
+/// Channel seed, zeroized on drop
+///
+/// The value is only readable with [`Seed::as_str`], `Debug` prints `Seed(..)`
+///
+#[derive(Clone)]
+pub struct Seed(String);
+
+impl Seed {
+    ///
+    /// Create the seed from its value
+    ///
+    pub fn new<S: Into<String>>(value: S) -> Self {
+        Seed(value.into())
+    }
+
+    ///
+    /// Value of the seed, for the Streams Author and Subscriber constructors
+    ///
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Seed {
+    fn from(value: &str) -> Self {
+        Seed::new(value)
+    }
+}
+
+impl From<String> for Seed {
+    fn from(value: String) -> Self {
+        Seed::new(value)
+    }
+}
+
+impl FromStr for Seed {
+    type Err = std::convert::Infallible;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(Seed::new(value))
+    }
+}
+
+impl Drop for Seed {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+impl PartialEq for Seed {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_bytes().ct_eq(other.0.as_bytes()).into()
+    }
+}
+
+impl Eq for Seed {}
+
+impl fmt::Debug for Seed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Seed(..)")
+    }
+}
+
+///
+/// Generate a random seed from the OS random number generator
+///
+pub fn generate() -> Seed {
+    generate_with(&mut OsRng)
+}
+
+///
+/// Generate a random seed with the RNG
+///
+pub fn generate_with<R: Rng + ?Sized>(rng: &mut R) -> Seed {
+    let mut entropy = Zeroizing::new([0u8; ENTROPY_BYTES]);
+    rng.fill_bytes(&mut *entropy);
+    from_entropy(&entropy)
+}
+
+///
+/// Seed of the entropy
+///
+pub fn from_entropy(entropy: &[u8; ENTROPY_BYTES]) -> Seed {
+    Seed(hex::encode(entropy))
+}
+
+///
+/// Entropy of the seed, only for the seeds generated by this module
+///
+pub fn to_entropy(seed: &Seed) -> anyhow::Result<Zeroizing<[u8; ENTROPY_BYTES]>> {
+    let mut entropy = Zeroizing::new([0u8; ENTROPY_BYTES]);
+    hex::decode_to_slice(seed.as_str(), &mut *entropy)
+        .map_err(|_| anyhow::anyhow!("The seed is not {} hex encoded bytes", ENTROPY_BYTES))?;
+    Ok(entropy)
+}
+
+///
+/// English BIP39 mnemonic of the seed
+///
+pub fn to_mnemonic(seed: &Seed) -> anyhow::Result<Zeroizing<String>> {
+    Mnemonic::from_entropy(&*to_entropy(seed)?)
+        .map(|mnemonic| Zeroizing::new(mnemonic.to_string()))
+        .map_err(|e| anyhow::anyhow!("{:#?}", e))
+}
+
+///
+/// Restore the seed from its English BIP39 mnemonic
+///
+pub fn from_mnemonic(words: &str) -> anyhow::Result<Seed> {
+    let mnemonic = Mnemonic::parse(Zeroizing::new(words.to_lowercase()).as_str())
+        .map_err(|e| anyhow::anyhow!("Invalid mnemonic: {}", e))?;
+    let entropy = Zeroizing::new(mnemonic.to_entropy());
+    if entropy.len() != ENTROPY_BYTES {
+        return Err(anyhow::anyhow!(
+            "The mnemonic has {} words, seeds have 24 words",
+            mnemonic.word_count()
+        ));
+    }
+    let mut bytes = Zeroizing::new([0u8; ENTROPY_BYTES]);
+    bytes.copy_from_slice(&entropy);
+    Ok(from_entropy(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{from_entropy, from_mnemonic, generate, to_mnemonic, Seed};
+
+    #[test]
+    fn mnemonic_roundtrip() {
+        let seed = generate();
+        assert_eq!(seed.as_str().len(), 64);
+        assert_ne!(seed, generate());
+
+        let words = to_mnemonic(&seed).unwrap();
+        assert_eq!(words.split_whitespace().count(), 24);
+        assert_eq!(from_mnemonic(&words).unwrap(), seed);
+        assert_eq!(from_mnemonic(&words.to_uppercase()).unwrap(), seed);
+    }
+
+    #[test]
+    fn known_mnemonic() {
+        // BIP39 test vector of 256 bits of zeros
+        let seed = from_entropy(&[0u8; 32]);
+        let words = to_mnemonic(&seed).unwrap();
+        assert!(words.starts_with("abandon abandon"));
+        assert!(words.ends_with("abandon art"));
+
+        assert!(to_mnemonic(&Seed::from("TRYTESEED9")).is_err());
+        assert!(from_mnemonic("abandon abandon abandon").is_err());
+        let twelve = "legal winner thank year wave sausage worth useful legal winner thank yellow";
+        assert!(from_mnemonic(twelve).is_err());
+    }
+
+    #[test]
+    fn redacted_seed() {
+        let seed = Seed::from("SECRETSEED9");
+        assert_eq!(format!("{:?}", seed), "Seed(..)");
+        assert_eq!(format!("{:?}", Some(&seed)), "Some(Seed(..))");
+        assert_eq!("SECRETSEED9".parse::<Seed>().unwrap(), seed);
+    }
+}
@@ -2,10 +2,23 @@
 //! Transport Module
 //!
 
+use crate::clock::{Clock, SystemClock};
+use crate::keystore::Keystore;
+use crate::payload::{
+    batch::BatchPayload,
+    chunk::{split, Reassembler},
+    cloudevents::CloudEvent,
+    registry::{Decoded, DecoderRegistry},
+    PacketPayload, PayloadError, PayloadSchema, PayloadSerializer,
+};
+use chrono::{DateTime, Utc};
 use iota_streams::{
     app::message::HasLink as _,
     app::transport::{
-        tangle::client::{Client, SendOptions},
+        tangle::{
+            client::{Client, SendOptions},
+            PAYLOAD_BYTES,
+        },
         TransportOptions,
     },
     app_channels::api::tangle::{
@@ -14,6 +27,7 @@ use iota_streams::{
     core::prelude::Rc,
     ddml::types::Bytes,
 };
+use serde::de::DeserializeOwned;
 use std::cell::RefCell;
 
 pub fn build_transport<'a>(uri: &'a str, node_mwm: u8) -> Rc<RefCell<Client>> {
@@ -27,6 +41,48 @@ pub fn build_transport<'a>(uri: &'a str, node_mwm: u8) -> Rc<RefCell<Client>> {
     transport
 }
 
+///
+/// Create the Author of the identity stored in the keystore
+///
+pub fn author_from_keystore<T: Transport>(
+    keystore: &Keystore,
+    name: &str,
+    encoding: &str,
+    multi_branching: bool,
+    transport: T,
+) -> anyhow::Result<Author<T>> {
+    let seed = keystore
+        .get(name)?
+        .ok_or_else(|| anyhow::anyhow!("Unknown identity {}", name))?;
+    Ok(Author::new(
+        seed.as_str(),
+        encoding,
+        PAYLOAD_BYTES,
+        multi_branching,
+        transport,
+    ))
+}
+
+///
+/// Create the Subscriber of the identity stored in the keystore
+///
+pub fn subscriber_from_keystore<T: Transport>(
+    keystore: &Keystore,
+    name: &str,
+    encoding: &str,
+    transport: T,
+) -> anyhow::Result<Subscriber<T>> {
+    let seed = keystore
+        .get(name)?
+        .ok_or_else(|| anyhow::anyhow!("Unknown identity {}", name))?;
+    Ok(Subscriber::new(
+        seed.as_str(),
+        encoding,
+        PAYLOAD_BYTES,
+        transport,
+    ))
+}
+
 pub enum FetchMessageContentType {
     SignedPacket,
     TaggedPacket,
@@ -103,3 +159,160 @@ pub async fn a_fetch_next_messages<T: Transport>(author: &mut Author<T>) -> Vec<
 
     messages
 }
+
+///
+/// Send the payload as linked tagged packets, split in fragments of at most `max_bytes`
+///
+/// Returns the link of the last fragment
+///
+pub async fn send_tagged_fragments<T, P>(
+    author: &mut Author<T>,
+    link: &Address,
+    payload: &P,
+    max_bytes: usize,
+) -> anyhow::Result<Address>
+where
+    T: Transport + Clone,
+    P: PacketPayload,
+{
+    let mut last_link = link.clone();
+    for fragment in split(payload, max_bytes)? {
+        let (msg, _) = author
+            .send_tagged_packet(&last_link, fragment.public_data(), fragment.masked_data())
+            .await
+            .map_err(|_| anyhow::anyhow!("Error to create tagged packet"))?;
+        last_link = msg;
+    }
+    Ok(last_link)
+}
+
+///
+/// Send the payload as linked signed packets, split in fragments of at most `max_bytes`
+///
+/// Returns the link of the last fragment
+///
+pub async fn send_signed_fragments<T, P>(
+    author: &mut Author<T>,
+    link: &Address,
+    payload: &P,
+    max_bytes: usize,
+) -> anyhow::Result<Address>
+where
+    T: Transport + Clone,
+    P: PacketPayload,
+{
+    let mut last_link = link.clone();
+    for fragment in split(payload, max_bytes)? {
+        let (msg, _) = author
+            .send_signed_packet(&last_link, fragment.public_data(), fragment.masked_data())
+            .await
+            .map_err(|_| anyhow::anyhow!("Error to create signed packet"))?;
+        last_link = msg;
+    }
+    Ok(last_link)
+}
+
+///
+/// Link and public and masked data of a reassembled payload
+///
+pub type ReassembledMessage = (Address, Result<(Bytes, Bytes), PayloadError>);
+
+///
+/// Rebuild the fragmented payloads of the fetched messages
+///
+/// Complete payloads are returned with the link of their last fragment, the reassembler keeps
+/// the incomplete ones until the next call. Broken fragment sequences are reported with the
+/// link of the offending fragment
+///
+pub fn reassemble_messages(
+    reassembler: &mut Reassembler,
+    messages: Vec<(Address, Bytes, Bytes)>,
+) -> Vec<ReassembledMessage> {
+    let mut complete = Vec::new();
+
+    for (link, public, masked) in messages {
+        match reassembler.push(&public, &masked) {
+            Ok(Some(payload)) => complete.push((link, Ok(payload))),
+            Ok(None) => {}
+            Err(e) => complete.push((link, Err(e))),
+        }
+    }
+    complete
+}
+
+///
+/// Decoded Message
+///
+pub struct DecodedMessage<M> {
+    /// Message link
+    pub link: Address,
+    /// Decoded public data
+    pub public: Result<Decoded<M>, PayloadError>,
+    /// Decoded masked data
+    pub masked: Result<Decoded<M>, PayloadError>,
+    /// Receipt time, to check the clock skew of the data
+    pub received: DateTime<Utc>,
+}
+
+///
+/// Decode the public and masked data of the fetched messages
+///
+/// Decode failures are reported per message, [`PayloadError::Empty`] means the message
+/// has no data of that kind. JSON data is validated against the schemas of the registry, the
+/// reports come with the decoded data or as [`PayloadError::SchemaViolation`]
+///
+pub fn decode_messages<M>(
+    registry: &DecoderRegistry<M>,
+    messages: Vec<(Address, Bytes, Bytes)>,
+) -> Vec<DecodedMessage<M>> {
+    let received = SystemClock.now();
+    messages
+        .into_iter()
+        .map(|(link, public, masked)| {
+            let channel = link.appinst.to_string();
+            DecodedMessage {
+                public: registry.decode_in(&channel, &public),
+                masked: registry.decode_in(&channel, &masked),
+                link,
+                received,
+            }
+        })
+        .collect()
+}
+
+///
+/// Unpack the individual readings of the fetched messages, sent alone or in batches
+///
+/// Every reading is returned with the link of its message, decode failures are reported
+/// once per message part
+///
+pub fn unpack_readings<S, T>(
+    messages: Vec<(Address, Bytes, Bytes)>,
+) -> Vec<(Address, Result<T, PayloadError>)>
+where
+    S: PayloadSerializer,
+    T: DeserializeOwned + PayloadSchema,
+{
+    let mut readings = Vec::new();
+
+    for (link, public, masked) in messages {
+        for data in [public, masked].iter() {
+            match BatchPayload::<T>::unpack::<S>(data) {
+                Ok(unpacked) => {
+                    readings.extend(unpacked.into_iter().map(|r| (link.clone(), Ok(r))))
+                }
+                Err(PayloadError::Empty) => {}
+                Err(e) => readings.push((link.clone(), Err(e))),
+            }
+        }
+    }
+    readings
+}
+
+///
+/// CloudEvent of the message data, the channel address is the event source and the message
+/// link its identifier
+///
+pub fn cloud_event(link: &Address, data: &Bytes) -> Result<CloudEvent, PayloadError> {
+    CloudEvent::from_payload(&data.0, &link.appinst.to_string(), &link.msgid.to_string())
+}